@@ -0,0 +1,511 @@
+// Copyright 2023 Salesforce, Inc. All rights reserved.
+use crate::encoding;
+use crate::settings::Attribute;
+use regex::Regex;
+
+/// How a condition compares the attribute values with the configured value.
+#[derive(Clone, Debug)]
+pub enum Matcher {
+    Exact(String),
+    Prefix(String),
+    /// Pattern where `*` matches any sequence of characters and `?` a single character.
+    Glob(String),
+    Regex(Regex),
+}
+
+impl Matcher {
+    pub fn matches(&self, value: &str) -> bool {
+        match self {
+            Matcher::Exact(expected) => expected == value,
+            Matcher::Prefix(prefix) => value.starts_with(prefix.as_str()),
+            Matcher::Glob(pattern) => glob_matches(pattern, value),
+            Matcher::Regex(regex) => regex.is_match(value),
+        }
+    }
+}
+
+/// Condition on a certificate attribute, satisfied when any of its values matches.
+#[derive(Clone, Debug)]
+pub struct Condition {
+    pub attribute: Attribute,
+    pub matcher: Matcher,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Effect {
+    Allow,
+    Deny,
+}
+
+/// Method and path of the request being authorized.
+#[derive(Clone, Copy, Debug)]
+pub struct Route<'a> {
+    pub method: &'a str,
+    pub path: &'a str,
+}
+
+/// How the rules that apply to a request decide whether it is allowed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RuleMatching {
+    /// The request is denied when any deny rule matches, or when there are allow rules and none of them matches.
+    All,
+    /// The first matching rule decides. When none matches, the request is denied if there are allow rules.
+    FirstMatch,
+    /// Only the rules with the most specific scope are considered, as in `All`.
+    MostSpecific,
+}
+
+/// Authorization rule, matching when all its conditions are satisfied.
+///
+/// The rule only applies to requests matching any of its path glob patterns and methods, all requests when empty.
+#[derive(Clone, Debug)]
+pub struct Rule {
+    pub name: String,
+    pub effect: Effect,
+    pub paths: Vec<String>,
+    pub methods: Vec<String>,
+    pub conditions: Vec<Condition>,
+}
+
+impl Rule {
+    /// Returns how specific the scope of the rule is for the route, `None` when the rule does not apply to it.
+    ///
+    /// Scopes are ranked by the number of literal characters of the matching path pattern, then by whether
+    /// they restrict the method.
+    fn specificity(&self, route: Route) -> Option<(usize, bool)> {
+        if !self.methods.is_empty()
+            && !self
+                .methods
+                .iter()
+                .any(|method| method.eq_ignore_ascii_case(route.method))
+        {
+            return None;
+        }
+
+        let path_specificity = if self.paths.is_empty() {
+            0
+        } else {
+            self.paths
+                .iter()
+                .filter(|pattern| glob_matches(pattern, route.path))
+                .map(|pattern| pattern.chars().filter(|c| *c != '*' && *c != '?').count())
+                .max()?
+        };
+
+        Some((path_specificity, !self.methods.is_empty()))
+    }
+
+    fn matches<'a, F>(&self, values: &F) -> bool
+    where
+        F: Fn(Attribute) -> Vec<&'a str>,
+    {
+        self.conditions.iter().all(|condition| {
+            values(condition.attribute)
+                .into_iter()
+                .any(|value| condition.matcher.matches(value))
+        })
+    }
+}
+
+/// Allow and deny rules evaluated against the certificate attributes.
+#[derive(Clone, Debug)]
+pub struct Authorization {
+    pub rules: Vec<Rule>,
+    pub matching: RuleMatching,
+}
+
+impl Default for Authorization {
+    fn default() -> Self {
+        Authorization {
+            rules: Vec::new(),
+            matching: RuleMatching::All,
+        }
+    }
+}
+
+impl Authorization {
+    /// Evaluates the rules that apply to the route with the attribute values returned by `values`,
+    /// returning the denial reason if any.
+    pub fn evaluate<'a, F>(&self, route: Route, values: F) -> Result<(), String>
+    where
+        F: Fn(Attribute) -> Vec<&'a str>,
+    {
+        let scoped: Vec<(&Rule, (usize, bool))> = self
+            .rules
+            .iter()
+            .filter_map(|rule| rule.specificity(route).map(|specificity| (rule, specificity)))
+            .collect();
+
+        let applicable: Vec<&Rule> = match self.matching {
+            RuleMatching::All | RuleMatching::FirstMatch => scoped.iter().map(|(rule, _)| *rule).collect(),
+            RuleMatching::MostSpecific => {
+                let most_specific = scoped.iter().map(|(_, specificity)| *specificity).max();
+                scoped
+                    .iter()
+                    .filter(|(_, specificity)| Some(*specificity) == most_specific)
+                    .map(|(rule, _)| *rule)
+                    .collect()
+            }
+        };
+
+        if self.matching == RuleMatching::FirstMatch {
+            if let Some(rule) = applicable.iter().find(|rule| rule.matches(&values)) {
+                return match rule.effect {
+                    Effect::Allow => Ok(()),
+                    Effect::Deny => Err(format!("Client certificate matches deny rule '{}'", rule.name)),
+                };
+            }
+        } else if let Some(rule) = applicable
+            .iter()
+            .find(|rule| rule.effect == Effect::Deny && rule.matches(&values))
+        {
+            return Err(format!("Client certificate matches deny rule '{}'", rule.name));
+        }
+
+        let mut allow = applicable.iter().filter(|rule| rule.effect == Effect::Allow).peekable();
+        if allow.peek().is_some() && !allow.any(|rule| rule.matches(&values)) {
+            return Err("Client certificate does not match any allow rule".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+/// Normalizes a request path before it is matched against the rule scopes, so encoded characters, repeated slashes
+/// and dot segments can not dodge a rule: `/%61dmin`, `//admin`, `/./admin` and `/x/../admin` all become `/admin`.
+pub fn normalize_path(path: &str) -> String {
+    let decoded = encoding::url_decode(path);
+    let mut segments: Vec<&str> = Vec::new();
+    for segment in decoded.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            segment => segments.push(segment),
+        }
+    }
+    let mut normalized = format!("/{}", segments.join("/"));
+    if decoded.ends_with('/') && !segments.is_empty() {
+        normalized.push('/');
+    }
+    normalized
+}
+
+/// Matches `value` against a glob `pattern` where `*` matches any sequence of characters and `?` a single character.
+pub fn glob_matches(pattern: &str, value: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let value: Vec<char> = value.chars().collect();
+    let (mut p, mut v) = (0, 0);
+    // Position of the last `*` in the pattern and of the value when it was reached.
+    let mut backtrack = None;
+
+    while v < value.len() {
+        if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, v));
+            p += 1;
+        } else if p < pattern.len() && (pattern[p] == '?' || pattern[p] == value[v]) {
+            p += 1;
+            v += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            v = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ANY_ROUTE: Route = Route {
+        method: "GET",
+        path: "/",
+    };
+
+    fn rule(name: &str, effect: Effect, conditions: Vec<(Attribute, Matcher)>) -> Rule {
+        Rule {
+            name: name.to_string(),
+            effect,
+            paths: Vec::new(),
+            methods: Vec::new(),
+            conditions: conditions
+                .into_iter()
+                .map(|(attribute, matcher)| Condition { attribute, matcher })
+                .collect(),
+        }
+    }
+
+    fn scoped(mut rule: Rule, paths: &[&str], methods: &[&str]) -> Rule {
+        rule.paths = paths.iter().map(|path| path.to_string()).collect();
+        rule.methods = methods.iter().map(|method| method.to_string()).collect();
+        rule
+    }
+
+    fn ou(value: &str) -> Vec<(Attribute, Matcher)> {
+        vec![(Attribute::OrganizationUnit, Matcher::Exact(value.to_string()))]
+    }
+
+    fn values(attribute: Attribute) -> Vec<&'static str> {
+        match attribute {
+            Attribute::Name => vec!["payments-service"],
+            Attribute::OrganizationUnit => vec!["Billing", "EMEA"],
+            Attribute::SanDns => vec!["payments.internal.example.com"],
+            Attribute::SpiffeTrustDomain => vec!["prod.example.org"],
+            Attribute::SpiffePath => vec!["/ns/payments/sa/api"],
+            _ => vec![],
+        }
+    }
+
+    #[test]
+    fn glob_matches_wildcards() {
+        assert!(glob_matches("*.example.com", "payments.internal.example.com"));
+        assert!(glob_matches("payments-*", "payments-service"));
+        assert!(glob_matches("pay?ents*", "payments"));
+        assert!(glob_matches("*", ""));
+        assert!(!glob_matches("*.example.com", "example.com"));
+        assert!(!glob_matches("payments", "payments-service"));
+    }
+
+    #[test]
+    fn normalizes_paths() {
+        assert_eq!(normalize_path("/%61dmin"), "/admin");
+        assert_eq!(normalize_path("//admin"), "/admin");
+        assert_eq!(normalize_path("/./admin"), "/admin");
+        assert_eq!(normalize_path("/x/../admin"), "/admin");
+        assert_eq!(normalize_path("/../../admin/users/"), "/admin/users/");
+        assert_eq!(normalize_path("/x/%2e%2e/admin"), "/admin");
+        assert_eq!(normalize_path(""), "/");
+    }
+
+    #[test]
+    fn deny_rules_apply_to_disguised_paths() {
+        let authorization = Authorization {
+            matching: RuleMatching::All,
+            rules: vec![scoped(rule("admin", Effect::Deny, ou("Billing")), &["/admin*"], &[])],
+        };
+
+        for disguised in ["/admin", "/%61dmin", "//admin", "/./admin", "/x/../admin"] {
+            let path = normalize_path(disguised);
+            let route = Route {
+                method: "GET",
+                path: &path,
+            };
+            assert!(authorization.evaluate(route, values).is_err(), "{}", disguised);
+        }
+    }
+
+    #[test]
+    fn allows_without_rules() {
+        assert_eq!(Authorization::default().evaluate(ANY_ROUTE, values), Ok(()));
+    }
+
+    #[test]
+    fn allows_when_an_allow_rule_matches() {
+        let authorization = Authorization {
+            matching: RuleMatching::All,
+            rules: vec![
+                rule(
+                    "sales",
+                    Effect::Allow,
+                    vec![(Attribute::OrganizationUnit, Matcher::Exact("Sales".to_string()))],
+                ),
+                rule(
+                    "billing",
+                    Effect::Allow,
+                    vec![(Attribute::OrganizationUnit, Matcher::Exact("Billing".to_string()))],
+                ),
+            ],
+        };
+
+        assert_eq!(authorization.evaluate(ANY_ROUTE, values), Ok(()));
+    }
+
+    #[test]
+    fn requires_all_conditions_of_a_rule() {
+        let authorization = Authorization {
+            matching: RuleMatching::All,
+            rules: vec![rule(
+                "billing-payments",
+                Effect::Allow,
+                vec![
+                    (Attribute::OrganizationUnit, Matcher::Exact("Billing".to_string())),
+                    (Attribute::Name, Matcher::Regex(Regex::new("^orders-.*$").unwrap())),
+                ],
+            )],
+        };
+
+        assert_eq!(
+            authorization.evaluate(ANY_ROUTE, values),
+            Err("Client certificate does not match any allow rule".to_string())
+        );
+    }
+
+    #[test]
+    fn denies_when_a_deny_rule_matches() {
+        let authorization = Authorization {
+            matching: RuleMatching::All,
+            rules: vec![
+                rule(
+                    "all",
+                    Effect::Allow,
+                    vec![(Attribute::Name, Matcher::Glob("*".to_string()))],
+                ),
+                rule(
+                    "internal",
+                    Effect::Deny,
+                    vec![(Attribute::SanDns, Matcher::Glob("*.internal.example.com".to_string()))],
+                ),
+            ],
+        };
+
+        assert_eq!(
+            authorization.evaluate(ANY_ROUTE, values),
+            Err("Client certificate matches deny rule 'internal'".to_string())
+        );
+    }
+
+    #[test]
+    fn conditions_on_missing_attributes_never_match() {
+        let authorization = Authorization {
+            matching: RuleMatching::All,
+            rules: vec![rule(
+                "no-email",
+                Effect::Deny,
+                vec![(Attribute::Email, Matcher::Glob("*".to_string()))],
+            )],
+        };
+
+        assert_eq!(authorization.evaluate(ANY_ROUTE, values), Ok(()));
+    }
+
+    #[test]
+    fn applies_rules_only_to_their_paths_and_methods() {
+        let authorization = Authorization {
+            matching: RuleMatching::All,
+            rules: vec![scoped(
+                rule("invoices", Effect::Allow, ou("Sales")),
+                &["/invoices*"],
+                &["POST"],
+            )],
+        };
+        let post_invoice = Route {
+            method: "POST",
+            path: "/invoices/42",
+        };
+        let get_invoice = Route {
+            method: "GET",
+            path: "/invoices/42",
+        };
+        let post_order = Route {
+            method: "POST",
+            path: "/orders",
+        };
+
+        assert!(authorization.evaluate(post_invoice, values).is_err());
+        assert_eq!(authorization.evaluate(get_invoice, values), Ok(()));
+        assert_eq!(authorization.evaluate(post_order, values), Ok(()));
+    }
+
+    #[test]
+    fn first_matching_rule_decides() {
+        let authorization = Authorization {
+            matching: RuleMatching::FirstMatch,
+            rules: vec![
+                rule("billing", Effect::Allow, ou("Billing")),
+                rule("emea", Effect::Deny, ou("EMEA")),
+            ],
+        };
+
+        assert_eq!(authorization.evaluate(ANY_ROUTE, values), Ok(()));
+    }
+
+    #[test]
+    fn first_match_denies_when_no_allow_rule_matches() {
+        let authorization = Authorization {
+            matching: RuleMatching::FirstMatch,
+            rules: vec![
+                rule("emea", Effect::Deny, ou("APAC")),
+                rule("sales", Effect::Allow, ou("Sales")),
+            ],
+        };
+
+        assert_eq!(
+            authorization.evaluate(ANY_ROUTE, values),
+            Err("Client certificate does not match any allow rule".to_string())
+        );
+    }
+
+    #[test]
+    fn most_specific_scope_decides() {
+        let authorization = Authorization {
+            matching: RuleMatching::MostSpecific,
+            rules: vec![
+                scoped(rule("everyone", Effect::Allow, ou("Sales")), &["/*"], &[]),
+                scoped(
+                    rule("billing-invoices", Effect::Allow, ou("Billing")),
+                    &["/invoices*"],
+                    &[],
+                ),
+                scoped(
+                    rule("billing-post-invoices", Effect::Deny, ou("EMEA")),
+                    &["/invoices*"],
+                    &["POST"],
+                ),
+            ],
+        };
+        let get_invoice = Route {
+            method: "GET",
+            path: "/invoices/42",
+        };
+        let post_invoice = Route {
+            method: "POST",
+            path: "/invoices/42",
+        };
+        let get_order = Route {
+            method: "GET",
+            path: "/orders/42",
+        };
+
+        assert_eq!(authorization.evaluate(get_invoice, values), Ok(()));
+        assert_eq!(
+            authorization.evaluate(post_invoice, values),
+            Err("Client certificate matches deny rule 'billing-post-invoices'".to_string())
+        );
+        assert!(authorization.evaluate(get_order, values).is_err());
+    }
+
+    #[test]
+    fn restricts_spiffe_trust_domain_and_path_prefix() {
+        let authorization = Authorization {
+            matching: RuleMatching::All,
+            rules: vec![rule(
+                "payments-workloads",
+                Effect::Allow,
+                vec![
+                    (
+                        Attribute::SpiffeTrustDomain,
+                        Matcher::Exact("prod.example.org".to_string()),
+                    ),
+                    (Attribute::SpiffePath, Matcher::Prefix("/ns/payments/".to_string())),
+                ],
+            )],
+        };
+        let other_namespace = Authorization {
+            matching: RuleMatching::All,
+            rules: vec![rule(
+                "orders-workloads",
+                Effect::Allow,
+                vec![(Attribute::SpiffePath, Matcher::Prefix("/ns/orders/".to_string()))],
+            )],
+        };
+
+        assert_eq!(authorization.evaluate(ANY_ROUTE, values), Ok(()));
+        assert!(other_namespace.evaluate(ANY_ROUTE, values).is_err());
+    }
+}
@@ -0,0 +1,370 @@
+// Copyright 2023 Salesforce, Inc. All rights reserved.
+use std::fmt;
+
+pub const BOOLEAN: u8 = 0x01;
+pub const INTEGER: u8 = 0x02;
+pub const BIT_STRING: u8 = 0x03;
+pub const OCTET_STRING: u8 = 0x04;
+pub const NULL: u8 = 0x05;
+pub const OBJECT_IDENTIFIER: u8 = 0x06;
+pub const ENUMERATED: u8 = 0x0a;
+pub const UTF8_STRING: u8 = 0x0c;
+pub const NUMERIC_STRING: u8 = 0x12;
+pub const PRINTABLE_STRING: u8 = 0x13;
+pub const TELETEX_STRING: u8 = 0x14;
+pub const IA5_STRING: u8 = 0x16;
+pub const UTC_TIME: u8 = 0x17;
+pub const GENERALIZED_TIME: u8 = 0x18;
+pub const VISIBLE_STRING: u8 = 0x1a;
+pub const UNIVERSAL_STRING: u8 = 0x1c;
+pub const BMP_STRING: u8 = 0x1e;
+pub const SEQUENCE: u8 = 0x30;
+pub const SET: u8 = 0x31;
+/// Explicit tag `[0]`, used for the version of a certificate.
+pub const CONTEXT_0: u8 = 0xa0;
+/// Explicit tag `[3]`, used for the extensions of a certificate.
+pub const CONTEXT_3: u8 = 0xa3;
+
+/// Reasons a DER encoding can not be decoded.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DerError {
+    Truncated,
+    InvalidLength,
+    UnexpectedTag { expected: u8, found: u8 },
+    InvalidTime,
+    InvalidObjectIdentifier,
+    InvalidString,
+    TooDeep,
+}
+
+impl fmt::Display for DerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DerError::Truncated => f.write_str("truncated DER"),
+            DerError::InvalidLength => f.write_str("invalid DER length"),
+            DerError::UnexpectedTag { expected, found } => {
+                write!(f, "expected DER tag 0x{:02x}, found 0x{:02x}", expected, found)
+            }
+            DerError::InvalidTime => f.write_str("invalid time"),
+            DerError::InvalidObjectIdentifier => f.write_str("invalid object identifier"),
+            DerError::InvalidString => f.write_str("invalid string"),
+            DerError::TooDeep => f.write_str("DER nested too deeply"),
+        }
+    }
+}
+
+/// A decoded tag-length-value element.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Tlv<'a> {
+    pub tag: u8,
+    pub value: &'a [u8],
+}
+
+/// Reads the elements of a DER encoding one after the other.
+#[derive(Clone, Debug)]
+pub struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(data: &'a [u8]) -> Reader<'a> {
+        Reader { data }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Returns the tag of the next element without reading it.
+    pub fn peek_tag(&self) -> Option<u8> {
+        self.data.first().copied()
+    }
+
+    /// Reads the next element. Only single byte tags are supported, which covers X.509.
+    pub fn read(&mut self) -> Result<Tlv<'a>, DerError> {
+        let (&tag, rest) = self.data.split_first().ok_or(DerError::Truncated)?;
+        let (&first, mut rest) = rest.split_first().ok_or(DerError::Truncated)?;
+
+        let length = if first < 0x80 {
+            first as usize
+        } else {
+            // Long form, DER forbids the indefinite length and lengths that do not fit 4 bytes here.
+            let count = (first & 0x7f) as usize;
+            if count == 0 || count > 4 || rest.len() < count {
+                return Err(DerError::InvalidLength);
+            }
+            let length = rest[..count]
+                .iter()
+                .fold(0usize, |length, byte| (length << 8) | *byte as usize);
+            rest = &rest[count..];
+            length
+        };
+
+        if rest.len() < length {
+            return Err(DerError::Truncated);
+        }
+        let (value, rest) = rest.split_at(length);
+        self.data = rest;
+        Ok(Tlv { tag, value })
+    }
+
+    /// Reads the next element, failing when it does not have the expected tag.
+    pub fn read_tag(&mut self, expected: u8) -> Result<&'a [u8], DerError> {
+        let tlv = self.read()?;
+        if tlv.tag != expected {
+            return Err(DerError::UnexpectedTag {
+                expected,
+                found: tlv.tag,
+            });
+        }
+        Ok(tlv.value)
+    }
+
+    /// Reads the next element when it has the given tag.
+    pub fn read_optional(&mut self, tag: u8) -> Result<Option<&'a [u8]>, DerError> {
+        match self.peek_tag() {
+            Some(found) if found == tag => self.read_tag(tag).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    /// Reads a UTCTime or a GeneralizedTime as seconds since the Unix epoch.
+    pub fn read_time(&mut self) -> Result<i64, DerError> {
+        let tlv = self.read()?;
+        let text = std::str::from_utf8(tlv.value).map_err(|_| DerError::InvalidTime)?;
+        let digits = text.strip_suffix('Z').ok_or(DerError::InvalidTime)?;
+        if !digits.is_ascii() {
+            return Err(DerError::InvalidTime);
+        }
+        let (year, rest) = match (tlv.tag, digits.len()) {
+            (UTC_TIME, 12) => {
+                // RFC 5280: two digits years from 50 are in the 20th century.
+                let year = number(&digits[..2])?;
+                (if year >= 50 { 1900 + year } else { 2000 + year }, &digits[2..])
+            }
+            (GENERALIZED_TIME, 14) => (number(&digits[..4])?, &digits[4..]),
+            (UTC_TIME, _) | (GENERALIZED_TIME, _) => return Err(DerError::InvalidTime),
+            (found, _) => {
+                return Err(DerError::UnexpectedTag {
+                    expected: UTC_TIME,
+                    found,
+                })
+            }
+        };
+
+        let month = number(&rest[0..2])?;
+        let day = number(&rest[2..4])?;
+        let hour = number(&rest[4..6])?;
+        let minute = number(&rest[6..8])?;
+        let second = number(&rest[8..10])?;
+        if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || minute > 59 || second > 60 {
+            return Err(DerError::InvalidTime);
+        }
+
+        Ok(days_from_civil(year, month, day) * 86_400 + hour * 3_600 + minute * 60 + second)
+    }
+}
+
+/// Decodes an object identifier to its dotted form, such as `2.5.4.3`.
+pub fn object_identifier(value: &[u8]) -> Result<String, DerError> {
+    let mut arcs = Vec::new();
+    let mut arc: u64 = 0;
+    for (index, byte) in value.iter().enumerate() {
+        if arc == 0 && *byte == 0x80 || arc > u64::MAX >> 7 {
+            return Err(DerError::InvalidObjectIdentifier);
+        }
+        arc = (arc << 7) | (byte & 0x7f) as u64;
+        if byte & 0x80 == 0 {
+            if arcs.is_empty() {
+                // The first subidentifier packs the first two arcs.
+                let first = (arc / 40).min(2);
+                arcs.push(first);
+                arcs.push(arc - first * 40);
+            } else {
+                arcs.push(arc);
+            }
+            arc = 0;
+        } else if index == value.len() - 1 {
+            return Err(DerError::InvalidObjectIdentifier);
+        }
+    }
+    if arcs.is_empty() {
+        return Err(DerError::InvalidObjectIdentifier);
+    }
+    Ok(arcs.iter().map(u64::to_string).collect::<Vec<_>>().join("."))
+}
+
+/// Encodes a DER element from its tag and value.
+pub fn encode(tag: u8, value: &[u8]) -> Vec<u8> {
+    let mut encoded = vec![tag];
+    let length = value.len().to_be_bytes();
+    match value.len() {
+        0..=0x7f => encoded.push(value.len() as u8),
+        _ => {
+            let significant: Vec<u8> = length.iter().copied().skip_while(|byte| *byte == 0).collect();
+            encoded.push(0x80 | significant.len() as u8);
+            encoded.extend(significant);
+        }
+    }
+    encoded.extend_from_slice(value);
+    encoded
+}
+
+/// Decodes an INTEGER as a decimal number, or as `0x` prefixed hex when it does not fit in 64 bits.
+pub fn integer(value: &[u8]) -> Result<String, DerError> {
+    match value.len() {
+        0 => Err(DerError::InvalidLength),
+        1..=8 => {
+            // Sign extend the two's complement value
+            let mut bytes = if value[0] & 0x80 != 0 { [0xff; 8] } else { [0; 8] };
+            bytes[8 - value.len()..].copy_from_slice(value);
+            Ok(i64::from_be_bytes(bytes).to_string())
+        }
+        _ => Ok(format!("0x{}", crate::encoding::hex(value))),
+    }
+}
+
+/// Decodes the string types found in X.509 names and extensions, octet strings are read as UTF-8.
+pub fn string(tag: u8, value: &[u8]) -> Result<String, DerError> {
+    match tag {
+        OCTET_STRING | UTF8_STRING | NUMERIC_STRING | PRINTABLE_STRING | IA5_STRING | VISIBLE_STRING => {
+            String::from_utf8(value.to_vec()).map_err(|_| DerError::InvalidString)
+        }
+        // Teletex strings are either UTF-8 or, in practice, Latin-1.
+        TELETEX_STRING => {
+            Ok(String::from_utf8(value.to_vec()).unwrap_or_else(|_| value.iter().map(|byte| *byte as char).collect()))
+        }
+        BMP_STRING => {
+            if value.len() % 2 != 0 {
+                return Err(DerError::InvalidString);
+            }
+            let units = value.chunks(2).map(|pair| u16::from_be_bytes([pair[0], pair[1]]));
+            std::char::decode_utf16(units)
+                .collect::<Result<String, _>>()
+                .map_err(|_| DerError::InvalidString)
+        }
+        UNIVERSAL_STRING => {
+            if value.len() % 4 != 0 {
+                return Err(DerError::InvalidString);
+            }
+            value
+                .chunks(4)
+                .map(|quad| std::char::from_u32(u32::from_be_bytes([quad[0], quad[1], quad[2], quad[3]])))
+                .collect::<Option<String>>()
+                .ok_or(DerError::InvalidString)
+        }
+        _ => Err(DerError::InvalidString),
+    }
+}
+
+fn number(digits: &str) -> Result<i64, DerError> {
+    if !digits.bytes().all(|byte| byte.is_ascii_digit()) {
+        return Err(DerError::InvalidTime);
+    }
+    digits.parse().map_err(|_| DerError::InvalidTime)
+}
+
+/// Days between the Unix epoch and a date of the proleptic Gregorian calendar.
+pub fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Date of the proleptic Gregorian calendar, as `(year, month, day)`, a number of days after the Unix epoch.
+pub fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    let year = year_of_era + era * 400;
+    (if month <= 2 { year + 1 } else { year }, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_short_and_long_lengths() {
+        let mut long = vec![0x04, 0x81, 0x80];
+        long.extend(vec![7; 128]);
+        let mut reader = Reader::new(&long);
+
+        assert_eq!(reader.read().unwrap().value.len(), 128);
+        assert!(reader.is_empty());
+
+        let mut reader = Reader::new(&[0x02, 0x01, 0x05, 0x30, 0x00]);
+        assert_eq!(reader.read_tag(INTEGER).unwrap(), &[5]);
+        assert_eq!(reader.read_optional(CONTEXT_0).unwrap(), None);
+        assert_eq!(reader.read_tag(SEQUENCE).unwrap(), &[] as &[u8]);
+    }
+
+    #[test]
+    fn rejects_truncated_elements() {
+        assert_eq!(Reader::new(&[0x30, 0x05, 0x00]).read(), Err(DerError::Truncated));
+        assert_eq!(Reader::new(&[0x30, 0x80]).read(), Err(DerError::InvalidLength));
+        assert_eq!(
+            Reader::new(&[0x02, 0x00]).read_tag(SEQUENCE),
+            Err(DerError::UnexpectedTag {
+                expected: SEQUENCE,
+                found: INTEGER
+            })
+        );
+    }
+
+    #[test]
+    fn reads_times() {
+        let mut reader = Reader::new(b"\x17\x0d491231235959Z\x17\x0d500101000000Z\x18\x0f20260101000000Z");
+
+        assert_eq!(reader.read_time().unwrap(), 2_524_607_999);
+        assert_eq!(reader.read_time().unwrap(), -631_152_000);
+        assert_eq!(reader.read_time().unwrap(), 1_767_225_600);
+        assert_eq!(
+            Reader::new(b"\x17\x0b2601010000Z").read_time(),
+            Err(DerError::InvalidTime)
+        );
+    }
+
+    #[test]
+    fn decodes_object_identifiers() {
+        assert_eq!(object_identifier(&[0x55, 0x04, 0x03]).unwrap(), "2.5.4.3");
+        assert_eq!(
+            object_identifier(&[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x09, 0x01]).unwrap(),
+            "1.2.840.113549.1.9.1"
+        );
+        assert_eq!(object_identifier(&[0x2a, 0x86]), Err(DerError::InvalidObjectIdentifier));
+    }
+
+    #[test]
+    fn decodes_integers() {
+        assert_eq!(integer(&[0x05]).unwrap(), "5");
+        assert_eq!(integer(&[0x00, 0xff]).unwrap(), "255");
+        assert_eq!(integer(&[0xff, 0x7f]).unwrap(), "-129");
+        assert_eq!(integer(&[0x01; 9]).unwrap(), "0x010101010101010101");
+        assert_eq!(integer(&[]), Err(DerError::InvalidLength));
+    }
+
+    #[test]
+    fn decodes_strings() {
+        assert_eq!(string(UTF8_STRING, "Jokér".as_bytes()).unwrap(), "Jokér");
+        assert_eq!(string(TELETEX_STRING, &[0x4a, 0xe9]).unwrap(), "Jé");
+        assert_eq!(string(BMP_STRING, &[0x00, 0x4a, 0x00, 0xe9]).unwrap(), "Jé");
+        assert_eq!(string(BOOLEAN, b"J"), Err(DerError::InvalidString));
+    }
+
+    #[test]
+    fn converts_dates() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(days_from_civil(2000, 3, 1), 11_017);
+        assert_eq!(civil_from_days(11_017), (2000, 3, 1));
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+    }
+}
@@ -0,0 +1,436 @@
+// Copyright 2023 Salesforce, Inc. All rights reserved.
+use crate::der::{self, Reader};
+use std::fmt;
+
+/// Characters that RFC 4514 allows to be escaped with a single backslash.
+const ESCAPABLE: &[u8] = b" \"#+,;<=>\\";
+
+/// A single `type=value` pair of a relative distinguished name.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AttributeTypeAndValue {
+    /// Attribute type as written in the DN, either a short name such as `CN` or a dotted OID.
+    pub attribute_type: String,
+    /// Attribute value with escapes, quotes and hex encoding removed.
+    pub value: String,
+}
+
+/// Relative distinguished name, one or more attributes joined with `+`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rdn {
+    pub attributes: Vec<AttributeTypeAndValue>,
+}
+
+/// Reasons a distinguished name can not be parsed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DnError {
+    InvalidAttributeType(String),
+    MissingEquals(usize),
+    InvalidEscape(usize),
+    InvalidHexValue(usize),
+    UnterminatedQuote(usize),
+    UnexpectedCharacter(usize),
+    InvalidUtf8,
+}
+
+impl fmt::Display for DnError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DnError::InvalidAttributeType(attribute_type) => write!(f, "invalid attribute type '{}'", attribute_type),
+            DnError::MissingEquals(position) => write!(f, "missing '=' after attribute type at position {}", position),
+            DnError::InvalidEscape(position) => write!(f, "invalid escape sequence at position {}", position),
+            DnError::InvalidHexValue(position) => write!(f, "invalid hex encoded value at position {}", position),
+            DnError::UnterminatedQuote(position) => write!(f, "unterminated quoted value at position {}", position),
+            DnError::UnexpectedCharacter(position) => write!(f, "unexpected character at position {}", position),
+            DnError::InvalidUtf8 => write!(f, "attribute value is not valid UTF-8"),
+        }
+    }
+}
+
+/// Parses a string representation of a distinguished name as described in RFC 4514.
+///
+/// RDNs are returned in the order they appear in the string. Quoted values, `;` separators
+/// and spaces around separators from RFC 1779 and RFC 2253 are accepted as well, as are
+/// `OID.`-prefixed attribute types.
+pub fn parse(dn: &str) -> Result<Vec<Rdn>, DnError> {
+    let mut parser = Parser {
+        input: dn.as_bytes(),
+        position: 0,
+    };
+    let mut rdns = Vec::new();
+
+    parser.skip_spaces();
+    if parser.at_end() {
+        return Ok(rdns);
+    }
+
+    loop {
+        let mut attributes = vec![parser.attribute_type_and_value()?];
+        while parser.eat(b'+') {
+            attributes.push(parser.attribute_type_and_value()?);
+        }
+        rdns.push(Rdn { attributes });
+
+        if parser.at_end() {
+            return Ok(rdns);
+        }
+        if !parser.eat(b',') && !parser.eat(b';') {
+            return Err(DnError::UnexpectedCharacter(parser.position));
+        }
+    }
+}
+
+struct Parser<'a> {
+    input: &'a [u8],
+    position: usize,
+}
+
+impl Parser<'_> {
+    fn at_end(&self) -> bool {
+        self.position >= self.input.len()
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.input.get(self.position).copied()
+    }
+
+    fn eat(&mut self, expected: u8) -> bool {
+        if self.peek() == Some(expected) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn skip_spaces(&mut self) {
+        while self.peek() == Some(b' ') {
+            self.position += 1;
+        }
+    }
+
+    fn at_separator(&self) -> bool {
+        matches!(self.peek(), None | Some(b',') | Some(b';') | Some(b'+'))
+    }
+
+    fn attribute_type_and_value(&mut self) -> Result<AttributeTypeAndValue, DnError> {
+        let attribute_type = self.attribute_type()?;
+        let value = self.value()?;
+        Ok(AttributeTypeAndValue { attribute_type, value })
+    }
+
+    fn attribute_type(&mut self) -> Result<String, DnError> {
+        self.skip_spaces();
+        let start = self.position;
+        while let Some(byte) = self.peek() {
+            if byte == b'=' || byte == b',' || byte == b'+' || byte == b';' {
+                break;
+            }
+            self.position += 1;
+        }
+        let raw = String::from_utf8_lossy(&self.input[start..self.position])
+            .trim()
+            .to_string();
+        if !self.eat(b'=') {
+            return Err(DnError::MissingEquals(self.position));
+        }
+
+        let attribute_type = match raw.get(..4) {
+            Some(prefix) if prefix.eq_ignore_ascii_case("oid.") => &raw[4..],
+            _ => raw.as_str(),
+        };
+        if is_descriptor(attribute_type) || is_numeric_oid(attribute_type) {
+            Ok(attribute_type.to_string())
+        } else {
+            Err(DnError::InvalidAttributeType(raw))
+        }
+    }
+
+    fn value(&mut self) -> Result<String, DnError> {
+        self.skip_spaces();
+        let value = match self.peek() {
+            Some(b'#') => self.hex_value()?,
+            Some(b'"') => self.quoted_value()?,
+            _ => return self.string_value(),
+        };
+        self.skip_spaces();
+        if self.at_separator() {
+            Ok(value)
+        } else {
+            Err(DnError::UnexpectedCharacter(self.position))
+        }
+    }
+
+    fn string_value(&mut self) -> Result<String, DnError> {
+        let mut bytes = Vec::new();
+        // Unescaped trailing spaces are not part of the value.
+        let mut significant = 0;
+        while !self.at_separator() {
+            if self.peek() == Some(b'\\') {
+                bytes.push(self.escape()?);
+                significant = bytes.len();
+            } else {
+                let byte = self.input[self.position];
+                self.position += 1;
+                bytes.push(byte);
+                if byte != b' ' {
+                    significant = bytes.len();
+                }
+            }
+        }
+        bytes.truncate(significant);
+        String::from_utf8(bytes).map_err(|_| DnError::InvalidUtf8)
+    }
+
+    fn quoted_value(&mut self) -> Result<String, DnError> {
+        let start = self.position;
+        self.position += 1;
+        let mut bytes = Vec::new();
+        loop {
+            match self.peek() {
+                None => return Err(DnError::UnterminatedQuote(start)),
+                Some(b'"') => {
+                    self.position += 1;
+                    break;
+                }
+                Some(b'\\') => bytes.push(self.escape()?),
+                Some(byte) => {
+                    self.position += 1;
+                    bytes.push(byte);
+                }
+            }
+        }
+        String::from_utf8(bytes).map_err(|_| DnError::InvalidUtf8)
+    }
+
+    fn hex_value(&mut self) -> Result<String, DnError> {
+        let start = self.position;
+        self.position += 1;
+        let mut bytes = Vec::new();
+        while let Some(high) = self.peek().and_then(hex_digit) {
+            let low = self.input.get(self.position + 1).copied().and_then(hex_digit);
+            match low {
+                Some(low) => bytes.push(high << 4 | low),
+                None => return Err(DnError::InvalidHexValue(start)),
+            }
+            self.position += 2;
+        }
+        if bytes.is_empty() {
+            return Err(DnError::InvalidHexValue(start));
+        }
+
+        // The value is the BER encoding of the attribute. Strings are decoded, anything else
+        // is kept in its hex form.
+        Ok(decode_ber_string(&bytes)
+            .unwrap_or_else(|| String::from_utf8_lossy(&self.input[start..self.position]).to_string()))
+    }
+
+    fn escape(&mut self) -> Result<u8, DnError> {
+        let start = self.position;
+        self.position += 1;
+        let first = self.peek().ok_or(DnError::InvalidEscape(start))?;
+        if let Some(high) = hex_digit(first) {
+            let low = self
+                .input
+                .get(self.position + 1)
+                .copied()
+                .and_then(hex_digit)
+                .ok_or(DnError::InvalidEscape(start))?;
+            self.position += 2;
+            Ok(high << 4 | low)
+        } else if ESCAPABLE.contains(&first) {
+            self.position += 1;
+            Ok(first)
+        } else {
+            Err(DnError::InvalidEscape(start))
+        }
+    }
+}
+
+fn hex_digit(byte: u8) -> Option<u8> {
+    (byte as char).to_digit(16).map(|digit| digit as u8)
+}
+
+fn is_descriptor(attribute_type: &str) -> bool {
+    let mut chars = attribute_type.chars();
+    matches!(chars.next(), Some(first) if first.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
+fn is_numeric_oid(attribute_type: &str) -> bool {
+    let mut arcs = 0;
+    for arc in attribute_type.split('.') {
+        if arc.is_empty() || !arc.bytes().all(|b| b.is_ascii_digit()) || (arc.len() > 1 && arc.starts_with('0')) {
+            return false;
+        }
+        arcs += 1;
+    }
+    arcs >= 2
+}
+
+/// Escapes an attribute value for the string representation of a DN, as described in RFC 4514.
+pub fn escape_value(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    let last = value.chars().count().saturating_sub(1);
+    for (index, c) in value.chars().enumerate() {
+        let leading = index == 0 && (c == ' ' || c == '#');
+        let trailing = index == last && c == ' ';
+        if leading || trailing || matches!(c, '"' | '+' | ',' | ';' | '<' | '>' | '\\') {
+            escaped.push('\\');
+        }
+        if c == '\0' {
+            escaped.push_str("\\00");
+        } else {
+            escaped.push(c);
+        }
+    }
+    escaped
+}
+
+/// Decodes a BER encoded string type, returning `None` for other types.
+fn decode_ber_string(bytes: &[u8]) -> Option<String> {
+    let mut reader = Reader::new(bytes);
+    let element = reader.read().ok()?;
+    if !reader.is_empty() {
+        return None;
+    }
+    der::string(element.tag, element.value).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pairs(dn: &str) -> Vec<Vec<(String, String)>> {
+        parse(dn)
+            .unwrap()
+            .into_iter()
+            .map(|rdn| {
+                rdn.attributes
+                    .into_iter()
+                    .map(|attribute| (attribute.attribute_type, attribute.value))
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn single(attribute_type: &str, value: &str) -> Vec<(String, String)> {
+        vec![(attribute_type.to_string(), value.to_string())]
+    }
+
+    #[test]
+    fn parses_rfc4514_simple_example() {
+        assert_eq!(
+            pairs("UID=jsmith,DC=example,DC=net"),
+            vec![single("UID", "jsmith"), single("DC", "example"), single("DC", "net")]
+        );
+    }
+
+    #[test]
+    fn parses_rfc4514_multi_valued_rdn() {
+        assert_eq!(
+            pairs("OU=Sales+CN=J.  Smith,DC=example,DC=net"),
+            vec![
+                vec![
+                    ("OU".to_string(), "Sales".to_string()),
+                    ("CN".to_string(), "J.  Smith".to_string())
+                ],
+                single("DC", "example"),
+                single("DC", "net"),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_rfc4514_escaped_characters() {
+        assert_eq!(
+            pairs(r#"CN=James \"Jim\" Smith\, III,DC=example,DC=net"#),
+            vec![
+                single("CN", r#"James "Jim" Smith, III"#),
+                single("DC", "example"),
+                single("DC", "net")
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_rfc4514_hex_escaped_control_character() {
+        assert_eq!(
+            pairs(r"CN=Before\0dAfter,DC=example,DC=net")[0],
+            single("CN", "Before\rAfter")
+        );
+    }
+
+    #[test]
+    fn parses_rfc4514_hex_encoded_value() {
+        assert_eq!(
+            pairs("1.3.6.1.4.1.1466.0=#04024869"),
+            vec![single("1.3.6.1.4.1.1466.0", "Hi")]
+        );
+    }
+
+    #[test]
+    fn parses_rfc4514_escaped_utf8() {
+        assert_eq!(pairs(r"CN=Lu\C4\8Di\C4\87"), vec![single("CN", "Lučić")]);
+    }
+
+    #[test]
+    fn keeps_escaped_comma_in_value() {
+        assert_eq!(
+            pairs(r"CN=Doe\, John,O=ACME"),
+            vec![single("CN", "Doe, John"), single("O", "ACME")]
+        );
+    }
+
+    #[test]
+    fn parses_quoted_values() {
+        assert_eq!(
+            pairs(r#"CN="Doe, John + \"JD\"", O=ACME"#),
+            vec![single("CN", r#"Doe, John + "JD""#), single("O", "ACME")]
+        );
+    }
+
+    #[test]
+    fn keeps_unknown_hex_encoded_values_as_hex() {
+        assert_eq!(pairs("2.5.4.45=#030200ff"), vec![single("2.5.4.45", "#030200ff")]);
+    }
+
+    #[test]
+    fn strips_oid_prefix_from_attribute_type() {
+        assert_eq!(pairs("OID.2.5.4.3=Joker"), vec![single("2.5.4.3", "Joker")]);
+    }
+
+    #[test]
+    fn trims_unescaped_spaces_only() {
+        assert_eq!(
+            pairs(r"CN = Joker \ , O=ACME ; C=US"),
+            vec![single("CN", "Joker  "), single("O", "ACME"), single("C", "US")]
+        );
+    }
+
+    #[test]
+    fn escapes_values() {
+        let value = r#" #Jo"ker+, \ "#;
+
+        assert_eq!(escape_value(value), r#"\ #Jo\"ker\+\, \\\ "#);
+        assert_eq!(pairs(&format!("CN={}", escape_value(value))), vec![single("CN", value)]);
+    }
+
+    #[test]
+    fn parses_empty_name() {
+        assert_eq!(parse("").unwrap(), Vec::new());
+    }
+
+    #[test]
+    fn rejects_malformed_names() {
+        assert_eq!(parse("CN"), Err(DnError::MissingEquals(2)));
+        assert_eq!(
+            parse("1CN=Joker"),
+            Err(DnError::InvalidAttributeType("1CN".to_string()))
+        );
+        assert_eq!(parse(r"CN=Jo\ker"), Err(DnError::InvalidEscape(5)));
+        assert_eq!(parse("CN=#04024"), Err(DnError::InvalidHexValue(3)));
+        assert_eq!(parse(r#"CN="Joker"#), Err(DnError::UnterminatedQuote(3)));
+        assert_eq!(parse(r#"CN="Joker" x"#), Err(DnError::UnexpectedCharacter(11)));
+        assert_eq!(parse(r"CN=\ff"), Err(DnError::InvalidUtf8));
+    }
+}
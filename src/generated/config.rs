@@ -1,6 +1,234 @@
 use serde::Deserialize;
 #[derive(Deserialize, Clone, Debug)]
+pub struct AttributeConstraints0Config {
+    #[serde(alias = "attribute")]
+    pub attribute: String,
+    #[serde(alias = "pattern")]
+    pub pattern: String,
+}
+#[derive(Deserialize, Clone, Debug)]
+pub struct AuthorizationRejection0Config {
+    #[serde(alias = "body")]
+    pub body: Option<String>,
+    #[serde(alias = "contentType")]
+    pub content_type: Option<String>,
+    #[serde(alias = "status")]
+    pub status: Option<i64>,
+}
+#[derive(Deserialize, Clone, Debug)]
+pub struct AuthorizationRules0Config {
+    #[serde(alias = "conditions")]
+    pub conditions: Vec<Conditions0Config>,
+    #[serde(alias = "effect")]
+    pub effect: Option<String>,
+    #[serde(alias = "methods")]
+    pub methods: Option<Vec<String>>,
+    #[serde(alias = "name")]
+    pub name: Option<String>,
+    #[serde(alias = "paths")]
+    pub paths: Option<Vec<String>>,
+}
+#[derive(Deserialize, Clone, Debug)]
+pub struct BoundTokenRejection0Config {
+    #[serde(alias = "body")]
+    pub body: Option<String>,
+    #[serde(alias = "contentType")]
+    pub content_type: Option<String>,
+    #[serde(alias = "status")]
+    pub status: Option<i64>,
+}
+#[derive(Deserialize, Clone, Debug)]
+pub struct Conditions0Config {
+    #[serde(alias = "attribute")]
+    pub attribute: String,
+    #[serde(alias = "matches")]
+    pub matches: Option<String>,
+    #[serde(alias = "value")]
+    pub value: String,
+}
+#[derive(Deserialize, Clone, Debug)]
+pub struct Crl0Config {
+    #[serde(alias = "failOpen")]
+    pub fail_open: Option<bool>,
+    #[serde(alias = "issuers")]
+    pub issuers: Vec<String>,
+    #[serde(alias = "path")]
+    pub path: Option<String>,
+    #[serde(alias = "pems")]
+    pub pems: Option<Vec<String>>,
+    #[serde(alias = "refreshInterval")]
+    pub refresh_interval: Option<i64>,
+    #[serde(alias = "service")]
+    pub service: Option<pdk::hl::Service>,
+}
+#[derive(Deserialize, Clone, Debug)]
+pub struct ForwardCertificate0Config {
+    #[serde(alias = "encoding")]
+    pub encoding: Option<String>,
+    #[serde(alias = "includeChain")]
+    pub include_chain: Option<bool>,
+}
+#[derive(Deserialize, Clone, Debug)]
+pub struct HeaderNames0Config {
+    #[serde(alias = "header")]
+    pub header: String,
+    #[serde(alias = "output")]
+    pub output: String,
+}
+#[derive(Deserialize, Clone, Debug)]
+pub struct IdentityHeader0Config {
+    #[serde(alias = "encoding")]
+    pub encoding: Option<String>,
+    #[serde(alias = "replaceAttributeHeaders")]
+    pub replace_attribute_headers: Option<bool>,
+}
+#[derive(Deserialize, Clone, Debug)]
+pub struct Jwt0Config {
+    #[serde(alias = "algorithm")]
+    pub algorithm: String,
+    #[serde(alias = "audience")]
+    pub audience: Option<String>,
+    #[serde(alias = "issuer")]
+    pub issuer: Option<String>,
+    #[serde(alias = "key")]
+    pub key: String,
+    #[serde(alias = "keyId")]
+    pub key_id: Option<String>,
+    #[serde(alias = "replaceAttributeHeaders")]
+    pub replace_attribute_headers: Option<bool>,
+    #[serde(alias = "ttl")]
+    pub ttl: Option<i64>,
+}
+#[derive(Deserialize, Clone, Debug)]
+pub struct MissingCertificate0Config {
+    #[serde(alias = "body")]
+    pub body: Option<String>,
+    #[serde(alias = "contentType")]
+    pub content_type: Option<String>,
+    #[serde(alias = "status")]
+    pub status: Option<i64>,
+}
+#[derive(Deserialize, Clone, Debug)]
+pub struct Ocsp0Config {
+    #[serde(alias = "cacheTtl")]
+    pub cache_ttl: Option<i64>,
+    #[serde(alias = "failOpen")]
+    pub fail_open: Option<bool>,
+    #[serde(alias = "issuers")]
+    pub issuers: Option<Vec<String>>,
+    #[serde(alias = "maxAge")]
+    pub max_age: Option<i64>,
+    #[serde(alias = "path")]
+    pub path: Option<String>,
+    #[serde(alias = "service")]
+    pub service: pdk::hl::Service,
+}
+#[derive(Deserialize, Clone, Debug)]
+pub struct OidHeaders0Config {
+    #[serde(alias = "header")]
+    pub header: String,
+    #[serde(alias = "oid")]
+    pub oid: String,
+    #[serde(alias = "source")]
+    pub source: Option<String>,
+}
+#[derive(Deserialize, Clone, Debug)]
+pub struct Pins0Config {
+    #[serde(alias = "fingerprint")]
+    pub fingerprint: Option<String>,
+    #[serde(alias = "name")]
+    pub name: Option<String>,
+    #[serde(alias = "publicKeyHash")]
+    pub public_key_hash: Option<String>,
+}
+#[derive(Deserialize, Clone, Debug)]
+pub struct Rejection0Config {
+    #[serde(alias = "body")]
+    pub body: Option<String>,
+    #[serde(alias = "contentType")]
+    pub content_type: Option<String>,
+    #[serde(alias = "status")]
+    pub status: Option<i64>,
+}
+#[derive(Deserialize, Clone, Debug)]
+pub struct UnverifiedCertificate0Config {
+    #[serde(alias = "body")]
+    pub body: Option<String>,
+    #[serde(alias = "contentType")]
+    pub content_type: Option<String>,
+    #[serde(alias = "status")]
+    pub status: Option<i64>,
+}
+#[derive(Deserialize, Clone, Debug)]
 pub struct Config {
-    #[serde(alias = "stringProperty")]
-    pub string_property: String,
+    #[serde(alias = "allowedExtendedKeyUsages")]
+    pub allowed_extended_key_usages: Option<Vec<String>>,
+    #[serde(alias = "attributeConstraints")]
+    pub attribute_constraints: Option<Vec<AttributeConstraints0Config>>,
+    #[serde(alias = "attributes")]
+    pub attributes: Option<Vec<String>>,
+    #[serde(alias = "authorizationMatching")]
+    pub authorization_matching: Option<String>,
+    #[serde(alias = "authorizationRejection")]
+    pub authorization_rejection: Option<AuthorizationRejection0Config>,
+    #[serde(alias = "authorizationRules")]
+    pub authorization_rules: Option<Vec<AuthorizationRules0Config>>,
+    #[serde(alias = "boundTokenHeader")]
+    pub bound_token_header: Option<String>,
+    #[serde(alias = "boundTokenRejection")]
+    pub bound_token_rejection: Option<BoundTokenRejection0Config>,
+    #[serde(alias = "checkBoundTokens")]
+    pub check_bound_tokens: Option<bool>,
+    #[serde(alias = "checkKeyUsage")]
+    pub check_key_usage: Option<bool>,
+    #[serde(alias = "checkValidity")]
+    pub check_validity: Option<bool>,
+    #[serde(alias = "crl")]
+    pub crl: Option<Crl0Config>,
+    #[serde(alias = "disabledOutputs")]
+    pub disabled_outputs: Option<Vec<String>>,
+    #[serde(alias = "expiryWarningDays")]
+    pub expiry_warning_days: Option<i64>,
+    #[serde(alias = "failureMode")]
+    pub failure_mode: Option<String>,
+    #[serde(alias = "forwardCertificate")]
+    pub forward_certificate: Option<ForwardCertificate0Config>,
+    #[serde(alias = "forwardClientCertDetails")]
+    pub forward_client_cert_details: Option<String>,
+    #[serde(alias = "headerNamespace")]
+    pub header_namespace: Option<String>,
+    #[serde(alias = "headerNames")]
+    pub header_names: Option<Vec<HeaderNames0Config>>,
+    #[serde(alias = "headerPrefix")]
+    pub header_prefix: Option<String>,
+    #[serde(alias = "identityHeader")]
+    pub identity_header: Option<IdentityHeader0Config>,
+    #[serde(alias = "jwt")]
+    pub jwt: Option<Jwt0Config>,
+    #[serde(alias = "missingCertificate")]
+    pub missing_certificate: Option<MissingCertificate0Config>,
+    #[serde(alias = "mode")]
+    pub mode: Option<String>,
+    #[serde(alias = "multiValueMode")]
+    pub multi_value_mode: Option<String>,
+    #[serde(alias = "multiValueSeparator")]
+    pub multi_value_separator: Option<String>,
+    #[serde(alias = "ocsp")]
+    pub ocsp: Option<Ocsp0Config>,
+    #[serde(alias = "oidHeaders")]
+    pub oid_headers: Option<Vec<OidHeaders0Config>>,
+    #[serde(alias = "peerCertificateAvailable")]
+    pub peer_certificate_available: Option<bool>,
+    #[serde(alias = "pins")]
+    pub pins: Option<Vec<Pins0Config>>,
+    #[serde(alias = "rejection")]
+    pub rejection: Option<Rejection0Config>,
+    #[serde(alias = "requireBoundTokens")]
+    pub require_bound_tokens: Option<bool>,
+    #[serde(alias = "requiredAttributes")]
+    pub required_attributes: Option<Vec<String>>,
+    #[serde(alias = "setCurrentClientCertDetails")]
+    pub set_current_client_cert_details: Option<Vec<String>>,
+    #[serde(alias = "unverifiedCertificate")]
+    pub unverified_certificate: Option<UnverifiedCertificate0Config>,
 }
@@ -0,0 +1,200 @@
+// Copyright 2023 Salesforce, Inc. All rights reserved.
+use crate::settings::{Attribute, IdentityEncoding};
+use crate::subject::ISSUER_ATTRIBUTES;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Version of the schema of the identity document, bumped on incompatible changes only.
+const VERSION: u32 = 1;
+
+/// Identity of the client as a single document. Every field is always present, unknown values are `null` and
+/// missing lists are empty, so consumers can rely on the schema.
+#[derive(Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Identity<'a> {
+    pub version: u32,
+    pub subject: Name<'a>,
+    pub issuer: Name<'a>,
+    pub san: Sans<'a>,
+    pub spiffe: Spiffe<'a>,
+    pub certificate: Metadata<'a>,
+}
+
+/// Distinguished name, with the values of its attributes by attribute key, such as `organizationUnit`.
+#[derive(Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Name<'a> {
+    pub dn: Option<&'a str>,
+    pub attributes: BTreeMap<&'static str, Vec<&'a str>>,
+}
+
+#[derive(Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Sans<'a> {
+    pub dns: Vec<&'a str>,
+    pub ip: Vec<&'a str>,
+    pub email: Vec<&'a str>,
+    pub uri: Vec<&'a str>,
+}
+
+#[derive(Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Spiffe<'a> {
+    pub id: Option<&'a str>,
+    pub trust_domain: Option<&'a str>,
+    pub path: Option<&'a str>,
+}
+
+#[derive(Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Metadata<'a> {
+    pub serial_number: Option<&'a str>,
+    pub fingerprint_sha256: Option<&'a str>,
+    pub fingerprint_sha1: Option<&'a str>,
+    pub not_before: Option<&'a str>,
+    pub not_after: Option<&'a str>,
+    pub days_until_expiry: Option<i64>,
+    pub extended_key_usages: Vec<&'a str>,
+}
+
+impl<'a> Identity<'a> {
+    /// Builds the document from the values of the given attributes, the others are left unset.
+    pub fn new(
+        attributes: &[Attribute],
+        subject_dn: Option<&'a str>,
+        values: impl Fn(Attribute) -> Vec<&'a str>,
+    ) -> Identity<'a> {
+        let mut identity = Identity {
+            version: VERSION,
+            ..Identity::default()
+        };
+        identity.subject.dn = subject_dn;
+
+        for attribute in attributes {
+            let values = values(*attribute);
+            let first = values.first().copied();
+            match attribute {
+                Attribute::SanDns => identity.san.dns = values,
+                Attribute::SanIp => identity.san.ip = values,
+                Attribute::SanEmail => identity.san.email = values,
+                Attribute::SanUri => identity.san.uri = values,
+                Attribute::SpiffeId => identity.spiffe.id = first,
+                Attribute::SpiffeTrustDomain => identity.spiffe.trust_domain = first,
+                Attribute::SpiffePath => identity.spiffe.path = first,
+                Attribute::CertificateSerial => identity.certificate.serial_number = first,
+                Attribute::FingerprintSha256 => identity.certificate.fingerprint_sha256 = first,
+                Attribute::FingerprintSha1 => identity.certificate.fingerprint_sha1 = first,
+                Attribute::NotBefore => identity.certificate.not_before = first,
+                Attribute::NotAfter => identity.certificate.not_after = first,
+                Attribute::DaysUntilExpiry => {
+                    identity.certificate.days_until_expiry = first.and_then(|days| days.parse().ok())
+                }
+                Attribute::ExtendedKeyUsage => identity.certificate.extended_key_usages = values,
+                Attribute::Issuer => identity.issuer.dn = first,
+                _ if values.is_empty() => {}
+                // Issuer attributes are keyed like the subject ones
+                _ => match ISSUER_ATTRIBUTES
+                    .iter()
+                    .find(|(issuer_attribute, _)| issuer_attribute == attribute)
+                {
+                    Some((_, subject_attribute)) => {
+                        identity.issuer.attributes.insert(subject_attribute.key(), values);
+                    }
+                    None => {
+                        identity.subject.attributes.insert(attribute.key(), values);
+                    }
+                },
+            }
+        }
+        identity
+    }
+
+    /// Serializes the document as the value of the identity header.
+    pub fn encode(&self, encoding: IdentityEncoding) -> String {
+        let json = serde_json::to_string(self).unwrap_or_default();
+        match encoding {
+            IdentityEncoding::Json => json,
+            IdentityEncoding::Base64Url => URL_SAFE_NO_PAD.encode(json),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    fn values(attribute: Attribute) -> Vec<&'static str> {
+        match attribute {
+            Attribute::Name => vec!["Joker"],
+            Attribute::OrganizationUnit => vec!["Billing", "Payments, EMEA"],
+            Attribute::SanDns => vec!["client.example.com"],
+            Attribute::SpiffeId => vec!["spiffe://example.com/billing"],
+            Attribute::SpiffeTrustDomain => vec!["example.com"],
+            Attribute::SpiffePath => vec!["/billing"],
+            Attribute::Issuer => vec!["CN=Internal CA,O=ACME"],
+            Attribute::IssuerName => vec!["Internal CA"],
+            Attribute::IssuerOrganization => vec!["ACME"],
+            Attribute::CertificateSerial => vec!["1a2b"],
+            Attribute::NotAfter => vec!["2125-01-01T00:00:00Z"],
+            Attribute::DaysUntilExpiry => vec!["36500"],
+            Attribute::ExtendedKeyUsage => vec!["clientAuth"],
+            _ => Vec::new(),
+        }
+    }
+
+    #[test]
+    fn follows_the_schema() {
+        let identity = Identity::new(&Attribute::ALL, Some("CN=Joker,OU=Billing,OU=Payments\\, EMEA"), values);
+        let document: Value = serde_json::from_str(&identity.encode(IdentityEncoding::Json)).unwrap();
+
+        assert_eq!(
+            document,
+            json!({
+                "version": 1,
+                "subject": {
+                    "dn": "CN=Joker,OU=Billing,OU=Payments\\, EMEA",
+                    "attributes": {"name": ["Joker"], "organizationUnit": ["Billing", "Payments, EMEA"]}
+                },
+                "issuer": {
+                    "dn": "CN=Internal CA,O=ACME",
+                    "attributes": {"name": ["Internal CA"], "organization": ["ACME"]}
+                },
+                "san": {"dns": ["client.example.com"], "ip": [], "email": [], "uri": []},
+                "spiffe": {"id": "spiffe://example.com/billing", "trustDomain": "example.com", "path": "/billing"},
+                "certificate": {
+                    "serialNumber": "1a2b",
+                    "fingerprintSha256": null,
+                    "fingerprintSha1": null,
+                    "notBefore": null,
+                    "notAfter": "2125-01-01T00:00:00Z",
+                    "daysUntilExpiry": 36500,
+                    "extendedKeyUsages": ["clientAuth"]
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn leaves_attributes_not_forwarded_unset() {
+        let identity = Identity::new(&[Attribute::Name], None, values);
+
+        assert_eq!(identity.subject.attributes.len(), 1);
+        assert_eq!(identity.issuer, Name::default());
+        assert_eq!(identity.spiffe, Spiffe::default());
+        assert_eq!(identity.certificate, Metadata::default());
+    }
+
+    #[test]
+    fn encodes_as_base64url() {
+        let identity = Identity::new(&[Attribute::Name], None, values);
+        let encoded = identity.encode(IdentityEncoding::Base64Url);
+
+        assert_eq!(
+            URL_SAFE_NO_PAD.decode(encoded).unwrap(),
+            identity.encode(IdentityEncoding::Json).into_bytes()
+        );
+    }
+}
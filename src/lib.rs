@@ -1,16 +1,49 @@
 // Copyright 2023 Salesforce, Inc. All rights reserved.
+mod authorization;
+mod der;
+mod dn;
+mod encoding;
 mod generated;
+mod identity;
+mod jwt;
+mod ocsp;
+mod pem;
+mod rejection;
+mod revocation;
+mod settings;
+mod signature;
+mod spiffe;
+mod subject;
+mod x509;
+mod xfcc;
+
 use anyhow::{anyhow, Result};
+use authorization::Route;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use encoding::CertificateEncoding;
+use generated::config::Config;
+use identity::Identity;
+use ocsp::{CertId, OcspCache};
+use pdk::hl::timer::{Clock, Timer};
 use pdk::hl::*;
-use std::collections::HashMap;
-
-const EMAIL_SUBJECT_PREAMBLE: &str = "emailAddress=";
-const NAME_SUBJECT_PREAMBLE: &str = "CN=";
-const ORGANIZATION_SUBJECT_PREAMBLE: &str = "O=";
-const ORGANIZATION_UNIT_PREAMBLE: &str = "OU=";
-const COUNTRY_PREAMBLE: &str = "C=";
-const LOCALITY_PREAMBLE: &str = "L=";
-const STATE_PREAMBLE: &str = "ST=";
+use pdk::logger;
+use revocation::{CrlStore, RevocationStatus};
+use serde_json::{json, Map, Value};
+use settings::{
+    Attribute, BoundTokens, CertificateForwarding, CrlSettings, FailureMode, Header, JwtSettings, Mode, MultiValueMode,
+    OcspSettings, OidHeader, OidSource, Pin, Settings,
+};
+use sha1::{Digest, Sha1};
+use sha2::Sha256;
+use signature::Signed;
+use spiffe::SpiffeId;
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::time::{SystemTime, UNIX_EPOCH};
+use subject::{format_name, issuer_from_rdns, parse_subject, subject_from_rdns, Issuer, Subject};
+use x509::{Certificate, KeyUsage, Validity};
+use xfcc::Detail;
 
 /// This function reads the property "path" from the StreamProperties and returns is as a String.
 fn read_property(stream: &StreamProperties, path: &[&str]) -> String {
@@ -18,16 +51,12 @@ fn read_property(stream: &StreamProperties, path: &[&str]) -> String {
     String::from_utf8_lossy(&bytes).to_string()
 }
 
-/// Struct that contains the data we are interested in extracted from the subject field.
-pub struct Subject {
-    name: Option<String>,
-    email: Option<String>,
-    organization: Option<String>,
-    organization_unit: Option<String>,
-    country: Option<String>,
-    locality: Option<String>,
-    state: Option<String>,
-    errors: Vec<String>,
+/// This function reads the boolean property "path" from the StreamProperties, a missing property is `false`.
+fn read_bool_property(stream: &StreamProperties, path: &[&str]) -> bool {
+    match stream.read_property(path) {
+        Some(bytes) => bytes == [1] || bytes == b"true",
+        None => false,
+    }
 }
 
 /// Struct for holding SAN attributes
@@ -36,90 +65,49 @@ pub struct SanAttributes {
     ip_addresses: Vec<String>,
     email_addresses: Vec<String>,
     uri_sans: Vec<String>,
+    spiffe_id: Option<SpiffeId>,
 }
 
-/// This function extracts the name, email, and additional attributes from the given subject field.
-fn parse_subject(subject_field: &str) -> Subject {
-    let split = subject_field.split(',');
-    let mut email = None;
-    let mut name = None;
-    let mut organization = None;
-    let mut organization_unit = None;
-    let mut country = None;
-    let mut locality = None;
-    let mut state = None;
-    let mut errors = Vec::new();
-
-    for segment in split {
-        let trimmed_segment = segment.trim();
-        // We extract the email.
-        if trimmed_segment.starts_with(EMAIL_SUBJECT_PREAMBLE) {
-            email = Some(trimmed_segment.split_at(EMAIL_SUBJECT_PREAMBLE.len()).1.to_string())
-        }
-        // We extract the name.
-        else if trimmed_segment.starts_with(NAME_SUBJECT_PREAMBLE) {
-            name = Some(trimmed_segment.split_at(NAME_SUBJECT_PREAMBLE.len()).1.to_string())
-        }
-        // Extract organization
-        else if trimmed_segment.starts_with(ORGANIZATION_SUBJECT_PREAMBLE) {
-            organization = Some(trimmed_segment.split_at(ORGANIZATION_SUBJECT_PREAMBLE.len()).1.to_string())
-        }
-        // Extract organization unit
-        else if trimmed_segment.starts_with(ORGANIZATION_UNIT_PREAMBLE) {
-            organization_unit = Some(trimmed_segment.split_at(ORGANIZATION_UNIT_PREAMBLE.len()).1.to_string())
-        }
-        // Extract country
-        else if trimmed_segment.starts_with(COUNTRY_PREAMBLE) {
-            country = Some(trimmed_segment.split_at(COUNTRY_PREAMBLE.len()).1.to_string())
-        }
-        // Extract locality/city
-        else if trimmed_segment.starts_with(LOCALITY_PREAMBLE) {
-            locality = Some(trimmed_segment.split_at(LOCALITY_PREAMBLE.len()).1.to_string())
-        }
-        // Extract state/province
-        else if trimmed_segment.starts_with(STATE_PREAMBLE) {
-            state = Some(trimmed_segment.split_at(STATE_PREAMBLE.len()).1.to_string())
-        }
-    }
-
-    // Check for missing required attributes and add errors
-    if name.is_none() {
-        errors.push("Common name missing from peer cert".to_string());
-    }
-    
-    if email.is_none() {
-        errors.push("Email address missing from peer cert".to_string());
-    }
+/// This function decodes the peer certificate PEM, `None` when the gateway does not provide it.
+fn decode_peer_certificate(pem: &str) -> Result<Option<Certificate>, String> {
+    let der = match encoding::certificate_der(pem) {
+        Ok(Some(der)) => der,
+        Ok(None) => return Ok(None),
+        Err(err) => return Err(format!("Malformed peer certificate PEM: {}", err)),
+    };
+    Certificate::parse(&der)
+        .map(Some)
+        .map_err(|err| format!("Malformed peer certificate: {}", err))
+}
 
-    Subject {
-        name,
-        email,
-        organization,
-        organization_unit,
-        country,
-        locality,
-        state,
-        errors,
+/// This function parses SAN attributes from the certificate, or from the connection properties without it
+fn parse_san_attributes(stream: &StreamProperties, peer_certificate: Option<&Certificate>) -> SanAttributes {
+    if let Some(certificate) = peer_certificate {
+        let names = &certificate.subject_alternative_names;
+        return SanAttributes {
+            dns_names: names.dns_names.clone(),
+            ip_addresses: names.ip_addresses.clone(),
+            email_addresses: names.email_addresses.clone(),
+            uri_sans: names.uris.clone(),
+            spiffe_id: None,
+        };
     }
-}
 
-/// This function parses SAN attributes from the certificate
-fn parse_san_attributes(stream: &StreamProperties) -> SanAttributes {
     let mut san_attributes = SanAttributes {
         dns_names: Vec::new(),
         ip_addresses: Vec::new(),
         email_addresses: Vec::new(),
         uri_sans: Vec::new(),
+        spiffe_id: None,
     };
 
-    // Parse DNS SANs
-    let dns_sans = read_property(stream, &["connection", "dns_sans_peer_certificate"]);
+    // Envoy provides the first DNS and URI SANs only
+    let dns_sans = read_property(stream, &["connection", "dns_san_peer_certificate"]);
     if !dns_sans.is_empty() {
         san_attributes.dns_names = dns_sans.split(',').map(|s| s.trim().to_string()).collect();
     }
 
-    // Parse URI SANs
-    let uri_sans = read_property(stream, &["connection", "uri_sans_peer_certificate"]);
+    let uri_sans = read_property(stream, &["connection", "uri_san_peer_certificate"]);
     if !uri_sans.is_empty() {
         san_attributes.uri_sans = uri_sans.split(',').map(|s| s.trim().to_string()).collect();
     }
@@ -139,95 +127,836 @@ fn parse_san_attributes(stream: &StreamProperties) -> SanAttributes {
     san_attributes
 }
 
+/// Struct for holding the attributes identifying the certificate itself
+#[derive(Default)]
+pub struct CertificateAttributes {
+    fingerprint_sha256: Option<String>,
+    fingerprint_sha1: Option<String>,
+    serial_number: Option<String>,
+    issuer: Option<String>,
+    issuer_attributes: Issuer,
+    validity: Option<Validity>,
+    not_before: Option<String>,
+    not_after: Option<String>,
+    days_until_expiry: Option<String>,
+    extended_key_usages: Vec<String>,
+}
+
+/// This function reads the fingerprints, serial number, issuer and validity of the certificate. Only the SHA-256
+/// fingerprint is known without the decoded certificate.
+fn parse_certificate_attributes(
+    stream: &StreamProperties,
+    peer_certificate: Option<&Certificate>,
+    now: i64,
+) -> CertificateAttributes {
+    let non_empty = |value: String| Some(value).filter(|value| !value.is_empty());
+
+    let (serial_number, issuer, issuer_attributes, validity) = match peer_certificate {
+        Some(certificate) => (
+            Some(certificate.serial_number.clone()),
+            Some(format_name(certificate.issuer.iter().rev())),
+            issuer_from_rdns(certificate.issuer.iter().rev()),
+            Some(certificate.validity),
+        ),
+        // Envoy does not provide the issuer, serial number and validity of the certificate
+        None => (None, None, Issuer::default(), None),
+    };
+
+    CertificateAttributes {
+        fingerprint_sha256: non_empty(read_property(stream, &["connection", "sha256_peer_certificate_digest"])),
+        // Envoy only provides the SHA-256 digest, the SHA-1 one is computed from the certificate
+        fingerprint_sha1: peer_certificate.map(|certificate| encoding::hex(&Sha1::digest(&certificate.der))),
+        serial_number,
+        issuer,
+        issuer_attributes,
+        validity,
+        not_before: validity.map(|validity| x509::format_time(validity.not_before)),
+        not_after: validity.map(|validity| x509::format_time(validity.not_after)),
+        days_until_expiry: validity.map(|validity| validity.days_until_expiry(now).to_string()),
+        extended_key_usages: peer_certificate
+            .and_then(|certificate| certificate.extended_key_usages().ok().flatten())
+            .unwrap_or_default()
+            .iter()
+            .map(|oid| x509::extended_key_usage_name(oid).to_string())
+            .collect(),
+    }
+}
+
+/// This function returns the values of the given attribute found in the certificate.
+fn attribute_values<'a>(
+    attribute: Attribute,
+    subject: &'a Subject,
+    san_attributes: &'a SanAttributes,
+    certificate: &'a CertificateAttributes,
+) -> Vec<&'a str> {
+    let values = match attribute {
+        Attribute::SanDns => &san_attributes.dns_names,
+        Attribute::SanIp => &san_attributes.ip_addresses,
+        Attribute::SanEmail => &san_attributes.email_addresses,
+        Attribute::SanUri => &san_attributes.uri_sans,
+        Attribute::SpiffeId => return san_attributes.spiffe_id.iter().map(|id| id.id.as_str()).collect(),
+        Attribute::SpiffeTrustDomain => {
+            return san_attributes.spiffe_id.iter().map(|id| id.trust_domain.as_str()).collect()
+        }
+        Attribute::SpiffePath => {
+            return san_attributes
+                .spiffe_id
+                .iter()
+                .map(|id| id.path.as_str())
+                .filter(|path| !path.is_empty())
+                .collect()
+        }
+        Attribute::FingerprintSha256 => return certificate.fingerprint_sha256.iter().map(String::as_str).collect(),
+        Attribute::FingerprintSha1 => return certificate.fingerprint_sha1.iter().map(String::as_str).collect(),
+        Attribute::CertificateSerial => return certificate.serial_number.iter().map(String::as_str).collect(),
+        Attribute::Issuer => return certificate.issuer.iter().map(String::as_str).collect(),
+        Attribute::NotBefore => return certificate.not_before.iter().map(String::as_str).collect(),
+        Attribute::NotAfter => return certificate.not_after.iter().map(String::as_str).collect(),
+        Attribute::DaysUntilExpiry => return certificate.days_until_expiry.iter().map(String::as_str).collect(),
+        Attribute::ExtendedKeyUsage => &certificate.extended_key_usages,
+        Attribute::IssuerName
+        | Attribute::IssuerEmail
+        | Attribute::IssuerOrganization
+        | Attribute::IssuerOrganizationUnit
+        | Attribute::IssuerOrganizationIdentifier
+        | Attribute::IssuerDomainComponent
+        | Attribute::IssuerSerialNumber
+        | Attribute::IssuerLocality
+        | Attribute::IssuerState
+        | Attribute::IssuerCountry => {
+            return certificate
+                .issuer_attributes
+                .field(attribute)
+                .into_iter()
+                .flatten()
+                .map(String::as_str)
+                .collect()
+        }
+        _ => return subject.field(attribute).into_iter().flatten().map(String::as_str).collect(),
+    };
+    values.iter().map(String::as_str).collect()
+}
+
+/// This function reports certificates used outside of their validity window.
+fn validity_errors(validity: Option<Validity>, now: i64) -> Option<String> {
+    let validity = validity?;
+    if now < validity.not_before {
+        return Some(format!("Peer cert not valid before {}", x509::format_time(validity.not_before)));
+    }
+    if now > validity.not_after {
+        return Some(format!("Peer cert expired on {}", x509::format_time(validity.not_after)));
+    }
+    None
+}
+
+/// This function looks up the values of an OID mapped to a header in the peer certificate.
+fn oid_values(certificate: &Certificate, oid_header: &OidHeader) -> Result<Vec<String>, String> {
+    let rdns = match oid_header.source {
+        OidSource::Extension => {
+            return certificate
+                .extension_values(&oid_header.oid)
+                .map(Option::unwrap_or_default)
+                .map_err(|err| format!("Malformed extension {} in peer cert: {}", oid_header.oid, err));
+        }
+        OidSource::Subject => &certificate.subject,
+        OidSource::Issuer => &certificate.issuer,
+    };
+    Ok(rdns
+        .iter()
+        .rev()
+        .flat_map(|rdn| rdn.attributes.iter())
+        .filter(|attribute| attribute.attribute_type == oid_header.oid)
+        .map(|attribute| attribute.value.clone())
+        .collect())
+}
+
+/// This function reports certificates not meant for TLS client authentication.
+fn key_usage_errors(peer_certificate: Option<&Certificate>, allowed_extended_key_usages: &[String]) -> Vec<String> {
+    let certificate = match peer_certificate {
+        Some(certificate) => certificate,
+        None => return vec!["Key usage of the peer cert can not be checked without its PEM".to_string()],
+    };
+    let mut errors = Vec::new();
+
+    // Without the extension, the certificate can be used for any purpose
+    match certificate.extended_key_usages() {
+        Ok(Some(oids)) if !oids.iter().any(|oid| allowed_extended_key_usages.contains(oid)) => {
+            let names: Vec<&str> = oids.iter().map(|oid| x509::extended_key_usage_name(oid)).collect();
+            errors.push(format!("Extended key usage '{}' not allowed in peer cert", names.join(",")));
+        }
+        Ok(_) => {}
+        Err(err) => errors.push(format!("Malformed extended key usage in peer cert: {}", err)),
+    }
+
+    // TLS client authentication signs the handshake, or agrees on a key with static Diffie-Hellman certificates
+    match certificate.key_usage() {
+        Ok(Some(key_usage))
+            if !key_usage.contains(KeyUsage::DIGITAL_SIGNATURE) && !key_usage.contains(KeyUsage::KEY_AGREEMENT) =>
+        {
+            errors.push("Key usage of peer cert does not allow client authentication".to_string());
+        }
+        Ok(_) => {}
+        Err(err) => errors.push(format!("Malformed key usage in peer cert: {}", err)),
+    }
+
+    errors
+}
+
+/// This function returns the current time in seconds since the Unix epoch.
+fn unix_time() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+/// This function lists the required attributes missing from the certificate and the values violating a constraint.
+fn attribute_errors(
+    settings: &Settings,
+    subject: &Subject,
+    san_attributes: &SanAttributes,
+    certificate: &CertificateAttributes,
+) -> Vec<String> {
+    let missing = settings
+        .required_attributes
+        .iter()
+        .filter(|attribute| attribute_values(**attribute, subject, san_attributes, certificate).is_empty())
+        .map(|attribute| format!("{} missing from peer cert", attribute.description()));
+
+    let violations = settings.attribute_constraints.iter().flat_map(|constraint| {
+        attribute_values(constraint.attribute, subject, san_attributes, certificate)
+            .into_iter()
+            .filter(move |value| !constraint.pattern.is_match(value))
+            .map(move |value| format!("{} '{}' not allowed in peer cert", constraint.attribute.description(), value))
+    });
+
+    missing.chain(violations).collect()
+}
+
+/// This function encodes the peer certificate, and its chain when enabled, for the certificate headers.
+fn forwarded_certificates(
+    stream: &StreamProperties,
+    forwarding: CertificateForwarding,
+) -> Result<Vec<(Header, String)>, String> {
+    let mut sources = vec![(Header::Certificate, "peer_certificate")];
+    if forwarding.include_chain {
+        sources.push((Header::CertificateChain, "peer_certificate_chain"));
+    }
+
+    let mut headers = Vec::new();
+    for (header, property) in sources {
+        let document = read_property(stream, &["connection", property]);
+        if document.is_empty() {
+            continue;
+        }
+        let encoded = encoding::encode_certificates(&document, forwarding.encoding)
+            .map_err(|err| format!("Malformed {} PEM: {}", property.replace('_', " "), err))?;
+        if !encoded.is_empty() {
+            headers.push((header, encoded));
+        }
+    }
+    Ok(headers)
+}
+
+/// This function builds the XFCC element describing the client certificate of the current connection, from its
+/// subject, PEM and SANs as read by the request filter.
+fn client_cert_element(
+    stream: &StreamProperties,
+    details: &[Detail],
+    subject: &str,
+    certificate_pem: &str,
+    san_attributes: &SanAttributes,
+) -> xfcc::Element {
+    let mut element = xfcc::Element::default();
+
+    let by = read_property(stream, &["connection", "uri_san_local_certificate"]);
+    if !by.is_empty() {
+        element.push("By", &by);
+    }
+    let hash = read_property(stream, &["connection", "sha256_peer_certificate_digest"]);
+    if !hash.is_empty() {
+        element.push("Hash", &hash);
+    }
+
+    for detail in details {
+        match detail {
+            Detail::Subject => {
+                if !subject.is_empty() {
+                    element.push("Subject", subject);
+                }
+            }
+            Detail::Cert | Detail::Chain => {
+                let (key, document) = match detail {
+                    Detail::Cert => ("Cert", certificate_pem.to_string()),
+                    _ => ("Chain", read_property(stream, &["connection", "peer_certificate_chain"])),
+                };
+                match encoding::encode_certificates(&document, CertificateEncoding::UrlEncodedPem) {
+                    Ok(encoded) if !encoded.is_empty() => element.push(key, &encoded),
+                    Ok(_) => {}
+                    Err(err) => logger::warn!("Not adding {} to the XFCC header: {}", key, err),
+                }
+            }
+            Detail::Uri => san_attributes.uri_sans.iter().for_each(|uri| element.push("URI", uri)),
+            Detail::Dns => san_attributes.dns_names.iter().for_each(|dns| element.push("DNS", dns)),
+        }
+    }
+
+    element
+}
+
+/// This function sanitizes, forwards or sets the XFCC header according to the configured mode.
+fn forward_client_cert(
+    handler: &dyn HeadersHandler,
+    stream: &StreamProperties,
+    settings: &Settings,
+    subject: &str,
+    certificate_pem: &str,
+    san_attributes: &SanAttributes,
+) {
+    let mode = match settings.forward_client_cert_details {
+        Some(mode) => mode,
+        None => return,
+    };
+
+    let existing = handler.header(xfcc::HEADER);
+    let mtls = read_bool_property(stream, &["connection", "mtls"]);
+    let element = || {
+        client_cert_element(stream, &settings.client_cert_details, subject, certificate_pem, san_attributes)
+    };
+    match xfcc::resolve(mode, existing.as_deref(), mtls, element) {
+        Some(value) => handler.set_header(xfcc::HEADER, value.as_str()),
+        None => handler.remove_header(xfcc::HEADER),
+    }
+}
+
+/// This function sets the header of an output, unless the output is disabled.
+fn set_output_header(handler: &dyn HeadersHandler, settings: &Settings, header: Header, value: &str) {
+    if let Some(name) = settings.header_name(header) {
+        handler.set_header(name, value);
+    }
+}
+
+/// This function writes the values of an attribute to the header according to the multi-value mode.
+fn set_values_header(handler: &dyn HeadersHandler, settings: &Settings, header: &str, values: &[&str]) {
+    match &settings.multi_value_mode {
+        MultiValueMode::Joined(separator) => handler.set_header(header, values.join(separator).as_str()),
+        MultiValueMode::Indexed => {
+            handler.set_header(header, values[0]);
+            if values.len() > 1 {
+                for (index, value) in values.iter().enumerate() {
+                    handler.set_header(&format!("{}-{}", header, index + 1), value);
+                }
+            }
+        }
+        MultiValueMode::Json => {
+            let json = serde_json::to_string(values).unwrap_or_default();
+            handler.set_header(header, json.as_str());
+        }
+    }
+}
+
+/// This function builds the claims of the JWT asserting the identity of the client, bound to its certificate.
+fn jwt_claims(
+    jwt: &JwtSettings,
+    subject: &Subject,
+    san_attributes: &SanAttributes,
+    certificate: &CertificateAttributes,
+    peer_certificate: Option<&Certificate>,
+    now: i64,
+) -> Value {
+    let first = |attributes: &[Attribute]| {
+        attributes
+            .iter()
+            .find_map(|attribute| attribute_values(*attribute, subject, san_attributes, certificate).first().copied())
+    };
+    let mut claims = Map::new();
+    let mut claim = |name: &str, value: Option<Value>| {
+        if let Some(value) = value {
+            claims.insert(name.to_string(), value);
+        }
+    };
+
+    claim("iss", jwt.issuer.as_ref().map(|issuer| json!(issuer)));
+    claim("sub", first(&[Attribute::SpiffeId, Attribute::Name]).map(|sub| json!(sub)));
+    claim("aud", jwt.audience.as_ref().map(|audience| json!(audience)));
+    claim("iat", Some(json!(now)));
+    let expires = now + jwt.ttl;
+    claim(
+        "exp",
+        Some(json!(certificate
+            .validity
+            .map_or(expires, |validity| expires.min(validity.not_after)))),
+    );
+    claim("email", first(&[Attribute::Email, Attribute::SanEmail]).map(|email| json!(email)));
+    claim("org", first(&[Attribute::Organization]).map(|org| json!(org)));
+
+    // Confirmation claim of RFC 8705, binding the JWT to the certificate
+    claim(
+        "cnf",
+        certificate_thumbprint(peer_certificate, certificate).map(|thumbprint| json!({ "x5t#S256": thumbprint })),
+    );
+    Value::Object(claims)
+}
+
+/// This function computes the base64url SHA-256 thumbprint of the peer certificate used by RFC 8705.
+fn certificate_thumbprint(peer_certificate: Option<&Certificate>, certificate: &CertificateAttributes) -> Option<String> {
+    let digest = match peer_certificate {
+        Some(peer_certificate) => Sha256::digest(&peer_certificate.der).to_vec(),
+        None => encoding::from_hex(certificate.fingerprint_sha256.as_deref()?)?,
+    };
+    Some(URL_SAFE_NO_PAD.encode(digest))
+}
+
+/// This function checks that the bearer token of the request, if any, is bound to the client certificate.
+fn bound_token_error(handler: &dyn HeadersHandler, bound_tokens: &BoundTokens, thumbprint: Option<&str>) -> Option<String> {
+    let token = handler.header(&bound_tokens.header)?;
+    let token = match token.split_once(' ') {
+        Some((scheme, token)) if scheme.eq_ignore_ascii_case("bearer") => token.trim(),
+        _ => token.trim(),
+    };
+    match (jwt::certificate_thumbprint(token), thumbprint) {
+        (Ok(Some(bound)), Some(thumbprint)) if bound == thumbprint => None,
+        (Ok(Some(_)), Some(_)) => Some("The token is bound to another certificate".to_string()),
+        (Ok(Some(_)), None) => Some("The token is bound to a certificate but none was presented".to_string()),
+        (Ok(None), _) if bound_tokens.required => Some("The token is not bound to a certificate".to_string()),
+        (Ok(None), _) => None,
+        (Err(err), _) if bound_tokens.required => Some(format!("The token can not be checked: {}", err)),
+        // Opaque tokens are left to the policies validating them
+        (Err(_), _) => None,
+    }
+}
+
+/// This function finds the pin matching the peer certificate, by its fingerprint or its public key.
+fn matching_pin<'a>(pins: &'a [Pin], stream: &StreamProperties, peer_certificate: Option<&Certificate>) -> Option<&'a Pin> {
+    let fingerprint = match peer_certificate {
+        Some(certificate) => encoding::hex(&Sha256::digest(&certificate.der)),
+        None => read_property(stream, &["connection", "sha256_peer_certificate_digest"]).to_ascii_lowercase(),
+    };
+    let public_key_hash =
+        peer_certificate.map(|certificate| Sha256::digest(&certificate.subject_public_key_info).to_vec());
+    pins.iter().find(|pin| {
+        pin.fingerprint.as_ref().is_some_and(|pinned| *pinned == fingerprint)
+            || pin.public_key_hash.is_some() && pin.public_key_hash == public_key_hash
+    })
+}
+
+/// This function looks up the revocation status of the peer certificate in the CRLs.
+fn crl_status(crls: &RefCell<CrlStore>, peer_certificate: Option<&Certificate>, now: i64) -> RevocationStatus {
+    match peer_certificate {
+        Some(certificate) => crls.borrow().status(
+            &format_name(certificate.issuer.iter().rev()),
+            &certificate.serial_number,
+            now,
+        ),
+        None => RevocationStatus::Unknown,
+    }
+}
+
+/// This function finds the certificate of the issuer of the peer certificate, in the configuration or the chain.
+/// The certificates of the chain must have signed the peer certificate, as the client can send any certificate.
+fn issuer_certificate(stream: &StreamProperties, ocsp: &OcspSettings, certificate: &Certificate) -> Option<Certificate> {
+    if let Some(issuer) = ocsp.issuers.iter().find(|issuer| issuer.subject == certificate.issuer) {
+        return Some(issuer.clone());
+    }
+    let signed = Signed::parse(&certificate.der).ok()?;
+    let chain = read_property(stream, &["connection", "peer_certificate_chain"]);
+    encoding::certificates_der(&chain)
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|certificate_der| Certificate::parse(certificate_der).ok())
+        .find(|issuer| issuer.subject == certificate.issuer && signed.verify(&issuer.subject_public_key_info).is_ok())
+}
+
+/// This function asks the OCSP responder for the revocation status of the peer certificate, unless it is cached.
+async fn ocsp_status(
+    client: &HttpClient,
+    ocsp: &OcspSettings,
+    cache: &RefCell<OcspCache>,
+    stream: &StreamProperties,
+    peer_certificate: Option<&Certificate>,
+    now: i64,
+) -> RevocationStatus {
+    let certificate = match peer_certificate {
+        Some(certificate) => certificate,
+        None => return RevocationStatus::Unknown,
+    };
+    let issuer = match issuer_certificate(stream, ocsp, certificate) {
+        Some(issuer) => issuer,
+        None => {
+            logger::warn!("Issuer of the peer cert not found, its OCSP status can not be asked");
+            return RevocationStatus::Unknown;
+        }
+    };
+    let cert_id = match CertId::new(certificate, &issuer) {
+        Ok(cert_id) => cert_id,
+        Err(err) => {
+            logger::warn!("Malformed public key in the issuer of the peer cert: {}", err);
+            return RevocationStatus::Unknown;
+        }
+    };
+    let key = cert_id.key();
+    if let Some(status) = cache.borrow().get(&key, now) {
+        return status;
+    }
+
+    let response = client
+        .request(&ocsp.service)
+        .path(&ocsp.path)
+        .headers(vec![("content-type", "application/ocsp-request")])
+        .body(&ocsp::request(&cert_id))
+        .post()
+        .await;
+    let response = match response {
+        Ok(response) if response.status_code() == 200 => response,
+        Ok(response) => {
+            logger::warn!("OCSP responder answered with status {}", response.status_code());
+            return RevocationStatus::Unknown;
+        }
+        Err(err) => {
+            logger::warn!("OCSP request failed: {}", err);
+            return RevocationStatus::Unknown;
+        }
+    };
+    match ocsp::parse_response(response.body(), &cert_id, &issuer, now, ocsp.max_age) {
+        Ok(single_response) => {
+            // Unknown statuses are asked again, the responder may learn about the certificate
+            if single_response.status != RevocationStatus::Unknown {
+                let expires = single_response
+                    .next_update
+                    .unwrap_or((now + ocsp.cache_ttl).min(single_response.this_update + ocsp.max_age));
+                cache.borrow_mut().insert(key, single_response.status, expires, now);
+            }
+            single_response.status
+        }
+        Err(err) => {
+            logger::warn!("Invalid OCSP response: {}", err);
+            RevocationStatus::Unknown
+        }
+    }
+}
+
+/// This function fetches the CRLs served by the configured service.
+async fn fetch_crls(
+    client: &HttpClient,
+    service: &Service,
+    path: &str,
+    issuers: &[Certificate],
+) -> Result<Vec<revocation::Crl>, String> {
+    let response = client
+        .request(service)
+        .path(path)
+        .get()
+        .await
+        .map_err(|err| format!("request failed: {}", err))?;
+    if response.status_code() != 200 {
+        return Err(format!("unexpected status {}", response.status_code()));
+    }
+    revocation::parse_crls(response.body(), issuers)
+}
+
+/// This function keeps the fetched CRLs up to date, until the policy is unloaded.
+async fn refresh_crls(client: &HttpClient, settings: &CrlSettings, crls: &RefCell<CrlStore>, timer: Timer) {
+    let (service, path) = match &settings.service {
+        Some(service) => service,
+        None => return,
+    };
+    loop {
+        match fetch_crls(client, service, path, &settings.issuers).await {
+            Ok(fetched) => crls.borrow_mut().update(fetched),
+            // The CRLs fetched before are kept until they expire
+            Err(err) => logger::warn!("Failed to fetch the CRLs: {}", err),
+        }
+        if !timer.next_tick().await {
+            break;
+        }
+    }
+}
+
+/// This function removes the headers managed by the policy sent by the client, so they can not be spoofed.
+fn strip_managed_headers(handler: &dyn HeadersHandler, settings: &Settings) {
+    for (name, _) in handler.headers() {
+        if settings.is_managed_header(&name) {
+            handler.remove_header(&name);
+        }
+    }
+}
+
 /// This filter reads the subject field from the peer certificate and adds attributes as headers.
-async fn request_filter(request_state: RequestState, stream: StreamProperties) -> Flow<()> {
+async fn request_filter(
+    request_state: RequestState,
+    stream: StreamProperties,
+    client: HttpClient,
+    settings: &Settings,
+    crls: &RefCell<CrlStore>,
+    ocsp_cache: &RefCell<OcspCache>,
+) -> Flow<()> {
     let headers_state = request_state.into_headers_state().await;
+    let handler = headers_state.handler();
+    strip_managed_headers(handler, settings);
+
     let subject_field = read_property(&stream, &["connection", "subject_peer_certificate"]);
-    
+    let peer_certificate_pem = read_property(&stream, &["connection", "peer_certificate"]);
+
+    // The decoded certificate is the source of truth, the string properties are the fallback without it
+    let mut errors = Vec::new();
+    let peer_certificate = decode_peer_certificate(&peer_certificate_pem).unwrap_or_else(|err| {
+        errors.push(err);
+        None
+    });
+    let mut san_attributes = parse_san_attributes(&stream, peer_certificate.as_ref());
+    forward_client_cert(
+        handler,
+        &stream,
+        settings,
+        &subject_field,
+        &peer_certificate_pem,
+        &san_attributes,
+    );
+
+    let method = headers_state.method();
+    let path = authorization::normalize_path(headers_state.path().split('?').next().unwrap_or_default());
+    let route = Route {
+        method: &method,
+        path: &path,
+    };
+
     // Set header to indicate if certificate is present
-    if subject_field.is_empty() {
-        headers_state.handler().set_header("X-Peer-Certificate-Present", "false");
+    if subject_field.is_empty() && peer_certificate_pem.is_empty() {
+        // Pinned APIs only accept the pinned certificates, whatever the mode
+        if settings.mode == Mode::Enforce || !settings.pins.is_empty() {
+            return Flow::Break(settings.missing_certificate.response("A client certificate is required"));
+        }
+        // Bound tokens can not be used without the certificate
+        if let Some(bound_tokens) = &settings.bound_tokens {
+            if let Some(reason) = bound_token_error(handler, bound_tokens, None) {
+                return Flow::Break(bound_tokens.rejection.response(&reason));
+            }
+        }
+        // Allow rules can not be satisfied without a certificate
+        if let Err(reason) = settings.authorization.evaluate(route, |_| Vec::new()) {
+            return Flow::Break(settings.authorization_rejection.response(&reason));
+        }
+        set_output_header(handler, settings, Header::CertificatePresent, "false");
         return Flow::Continue(());
     }
-    
-    headers_state.handler().set_header("X-Peer-Certificate-Present", "true");
-    
-    // Parse subject and set headers
-    let subject = parse_subject(&subject_field);
-    
-    // Set basic subject headers if available
-    if let Some(name) = &subject.name {
-        headers_state.handler().set_header("X-Peer-Name", name);
+
+    // The connection is mTLS only once the downstream presented a certificate that passed validation
+    if settings.mode == Mode::Enforce && !read_bool_property(&stream, &["connection", "mtls"]) {
+        return Flow::Break(settings.unverified_certificate.response("The client certificate could not be verified"));
     }
-    
-    if let Some(email) = &subject.email {
-        headers_state.handler().set_header("X-Peer-Email", email);
+
+    // Check the pins before setting any header
+    if !settings.pins.is_empty() {
+        match matching_pin(&settings.pins, &stream, peer_certificate.as_ref()) {
+            Some(pin) => set_output_header(handler, settings, Header::PinMatched, &pin.name),
+            None => {
+                return Flow::Break(
+                    settings
+                        .unverified_certificate
+                        .response("The client certificate does not match any pinned fingerprint or public key"),
+                )
+            }
+        }
     }
-    
-    // Set optional subject headers if available
-    if let Some(org) = &subject.organization {
-        headers_state.handler().set_header("X-Peer-Organization", org);
+
+    set_output_header(handler, settings, Header::CertificatePresent, "true");
+    let subject = match &peer_certificate {
+        Some(certificate) => subject_from_rdns(certificate.subject.iter().rev()),
+        None => parse_subject(&subject_field).unwrap_or_else(|err| {
+            errors.push(format!("Malformed subject in peer cert: {}", err));
+            Subject::default()
+        }),
+    };
+    let now = unix_time();
+
+    // Reject revoked certificates, OCSP is only asked about the certificates the CRLs do not cover
+    let mut revocation = settings
+        .crl
+        .as_ref()
+        .map(|crl| (crl_status(crls, peer_certificate.as_ref(), now), crl.fail_open));
+    if let Some(ocsp) = &settings.ocsp {
+        if matches!(revocation, None | Some((RevocationStatus::Unknown, _))) {
+            let status = ocsp_status(&client, ocsp, ocsp_cache, &stream, peer_certificate.as_ref(), now).await;
+            revocation = Some((status, ocsp.fail_open));
+        }
     }
-    
-    if let Some(ou) = &subject.organization_unit {
-        headers_state.handler().set_header("X-Peer-OrganizationUnit", ou);
+    match revocation {
+        None | Some((RevocationStatus::Good, _)) => {}
+        Some((RevocationStatus::Revoked, _)) => {
+            return Flow::Break(settings.unverified_certificate.response("The client certificate has been revoked"))
+        }
+        Some((RevocationStatus::Unknown, true)) => {
+            logger::warn!("Revocation status of client certificate '{}' is unknown", subject_field)
+        }
+        Some((RevocationStatus::Unknown, false)) => {
+            return Flow::Break(
+                settings
+                    .unverified_certificate
+                    .response("The revocation status of the client certificate is unknown"),
+            )
+        }
     }
-    
-    if let Some(country) = &subject.country {
-        headers_state.handler().set_header("X-Peer-Country", country);
+
+    match spiffe::from_uri_sans(&san_attributes.uri_sans) {
+        Ok(spiffe_id) => san_attributes.spiffe_id = spiffe_id,
+        Err(err) => errors.push(err),
     }
-    
-    if let Some(locality) = &subject.locality {
-        headers_state.handler().set_header("X-Peer-Locality", locality);
+    let certificate = parse_certificate_attributes(&stream, peer_certificate.as_ref(), now);
+
+    // Tokens stolen from the client are useless without its certificate
+    if let Some(bound_tokens) = &settings.bound_tokens {
+        let thumbprint = certificate_thumbprint(peer_certificate.as_ref(), &certificate);
+        if let Some(reason) = bound_token_error(handler, bound_tokens, thumbprint.as_deref()) {
+            return Flow::Break(bound_tokens.rejection.response(&reason));
+        }
     }
-    
-    if let Some(state) = &subject.state {
-        headers_state.handler().set_header("X-Peer-State", state);
+    if settings.check_validity {
+        errors.extend(validity_errors(certificate.validity, now));
     }
-    
-    // Add error messages if there are any
-    if !subject.errors.is_empty() {
-        headers_state.handler().set_header("X-Peer-Certificate-Errors", subject.errors.join("; ").as_str());
+    if let Some(allowed_extended_key_usages) = &settings.allowed_extended_key_usages {
+        errors.extend(key_usage_errors(peer_certificate.as_ref(), allowed_extended_key_usages));
     }
-    
-    // Parse and set SAN attributes
-    let san_attributes = parse_san_attributes(&stream);
-    
-    // Add DNS SANs
-    if !san_attributes.dns_names.is_empty() {
-        headers_state.handler().set_header("X-Peer-SAN-DNS", san_attributes.dns_names.join(",").as_str());
-        // Add first DNS SAN as a separate header for convenience
-        if let Some(primary_dns) = san_attributes.dns_names.first() {
-            headers_state.handler().set_header("X-Peer-Primary-DNS", primary_dns);
+    let mut oid_headers = Vec::new();
+    for oid_header in &settings.oid_headers {
+        match peer_certificate.as_ref().map(|certificate| oid_values(certificate, oid_header)) {
+            Some(Ok(values)) => oid_headers.push((oid_header.header.as_str(), values)),
+            Some(Err(err)) => errors.push(err),
+            None => errors.push("OID headers can not be set without the PEM of the peer cert".to_string()),
         }
     }
-    
-    // Add IP SANs
-    if !san_attributes.ip_addresses.is_empty() {
-        headers_state.handler().set_header("X-Peer-SAN-IP", san_attributes.ip_addresses.join(",").as_str());
-        // Add first IP SAN as a separate header
-        if let Some(primary_ip) = san_attributes.ip_addresses.first() {
-            headers_state.handler().set_header("X-Peer-Primary-IP", primary_ip);
+    let certificates = match settings.forward_certificate {
+        Some(forwarding) => forwarded_certificates(&stream, forwarding).unwrap_or_else(|err| {
+            errors.push(err);
+            Vec::new()
+        }),
+        None => Vec::new(),
+    };
+
+    // Check the required attributes and their constraints
+    errors.extend(attribute_errors(settings, &subject, &san_attributes, &certificate));
+    // The same malformed PEM can be reported by several outputs
+    let mut reported = HashSet::new();
+    errors.retain(|err| reported.insert(err.clone()));
+    if !errors.is_empty() {
+        if settings.failure_mode == FailureMode::Reject {
+            return Flow::Break(settings.rejection.response(&errors.join("; ")));
         }
+        set_output_header(handler, settings, Header::Errors, errors.join("; ").as_str());
     }
-    
-    // Add Email SANs (might duplicate the subject email, but included for completeness)
-    if !san_attributes.email_addresses.is_empty() {
-        headers_state.handler().set_header("X-Peer-SAN-Email", san_attributes.email_addresses.join(",").as_str());
+
+    // Check the authorization rules
+    let values = |attribute| attribute_values(attribute, &subject, &san_attributes, &certificate);
+    if let Err(reason) = settings.authorization.evaluate(route, values) {
+        return Flow::Break(settings.authorization_rejection.response(&reason));
     }
-    
-    // Add URI SANs
-    if !san_attributes.uri_sans.is_empty() {
-        headers_state.handler().set_header("X-Peer-SAN-URI", san_attributes.uri_sans.join(",").as_str());
+
+    // Warn about certificates about to expire, so their owners can renew them in time
+    if let (Some(warning_days), Some(validity)) = (settings.expiry_warning_days, certificate.validity) {
+        let days_until_expiry = validity.days_until_expiry(now);
+        if (0..=warning_days).contains(&days_until_expiry) {
+            logger::warn!(
+                "Client certificate '{}' expires in {} days, on {}",
+                subject_field,
+                days_until_expiry,
+                x509::format_time(validity.not_after)
+            );
+            set_output_header(handler, settings, Header::ExpiryWarning, "true");
+        }
     }
-    
+
+    // Assert the identity of the client to backends validating JWTs
+    if let Some(jwt) = &settings.jwt {
+        let claims = jwt_claims(jwt, &subject, &san_attributes, &certificate, peer_certificate.as_ref(), now);
+        let token = jwt::encode(&jwt.key, jwt.key_id.as_deref(), &claims);
+        set_output_header(handler, settings, Header::Jwt, &token);
+    }
+
+    // Set the identity as a single document, keeping the values with commas intact
+    if let Some(identity_header) = settings.identity_header {
+        let subject_dn = match &peer_certificate {
+            Some(certificate) => Some(format_name(certificate.subject.iter().rev())),
+            None => Some(subject_field.clone()).filter(|dn| !dn.is_empty()),
+        };
+        let identity = Identity::new(&settings.attributes, subject_dn.as_deref(), |attribute| {
+            attribute_values(attribute, &subject, &san_attributes, &certificate)
+        });
+        set_output_header(handler, settings, Header::Identity, &identity.encode(identity_header.encoding));
+    }
+
+    // Set a header for every configured attribute found in the certificate, unless the JWT or the identity
+    // header replace them
+    let replaced = settings.jwt.as_ref().is_some_and(|jwt| jwt.replace_attribute_headers)
+        || settings
+            .identity_header
+            .is_some_and(|identity_header| identity_header.replace_attribute_headers);
+    let attributes = if replaced { &[] } else { settings.attributes.as_slice() };
+    for attribute in attributes {
+        let values = attribute_values(*attribute, &subject, &san_attributes, &certificate);
+        if values.is_empty() {
+            continue;
+        }
+
+        if let Some(header) = settings.header_name(attribute.header()) {
+            set_values_header(handler, settings, header, &values);
+        }
+
+        // Add the first DNS and IP SANs as separate headers for convenience
+        match attribute {
+            Attribute::SanDns => set_output_header(handler, settings, Header::PrimaryDns, values[0]),
+            Attribute::SanIp => set_output_header(handler, settings, Header::PrimaryIp, values[0]),
+            _ => {}
+        }
+    }
+
+    // Set the headers mapped to OIDs
+    for (header, values) in &oid_headers {
+        if !values.is_empty() {
+            let values: Vec<&str> = values.iter().map(String::as_str).collect();
+            set_values_header(handler, settings, header, &values);
+        }
+    }
+
+    // Forward the certificate for upstreams doing their own checks
+    for (header, value) in certificates {
+        set_output_header(handler, settings, header, value.as_str());
+    }
+
     // Always continue the flow
     Flow::Continue(())
 }
 
 #[entrypoint]
-async fn configure(launcher: Launcher) -> Result<()> {
-    let filter = on_request(request_filter);
-    launcher.launch(filter).await?;
+async fn configure(
+    launcher: Launcher,
+    Configuration(bytes): Configuration,
+    client: HttpClient,
+    clock: Clock,
+) -> Result<()> {
+    let config: Config = serde_json::from_slice(&bytes).map_err(|err| {
+        anyhow!(
+            "Failed to parse configuration '{}'. Cause: {}",
+            String::from_utf8_lossy(&bytes),
+            err
+        )
+    })?;
+    let settings = Settings::from_config(&config)?;
+
+    let crls = RefCell::new(CrlStore::new(
+        settings.crl.iter().flat_map(|crl| crl.crls.iter().cloned()).collect(),
+    ));
+
+    let ocsp_cache = RefCell::new(OcspCache::default());
+
+    let filter = on_request(|request_state, stream, client| {
+        request_filter(request_state, stream, client, &settings, &crls, &ocsp_cache)
+    });
+    match &settings.crl {
+        Some(crl) if crl.service.is_some() => {
+            let timer = clock.period(crl.refresh_interval);
+            let (_, launched) = futures::join!(refresh_crls(&client, crl, &crls, timer), launcher.launch(filter));
+            launched?;
+        }
+        _ => launcher.launch(filter).await?,
+    }
     Ok(())
-}
\ No newline at end of file
+}
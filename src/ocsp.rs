@@ -0,0 +1,430 @@
+// Copyright 2023 Salesforce, Inc. All rights reserved.
+use crate::der::{
+    self, DerError, Reader, BIT_STRING, CONTEXT_0, ENUMERATED, GENERALIZED_TIME, INTEGER, NULL, OBJECT_IDENTIFIER,
+    OCTET_STRING, SEQUENCE,
+};
+use crate::encoding;
+use crate::revocation::RevocationStatus;
+use crate::signature::Signed;
+use crate::x509::Certificate;
+use sha1::{Digest, Sha1};
+use std::collections::HashMap;
+use std::fmt;
+
+/// DER encoded OID of SHA-1, the hash algorithm of the certificate IDs.
+const SHA1_OID: &[u8] = &[0x2b, 0x0e, 0x03, 0x02, 0x1a];
+const BASIC_RESPONSE: &str = "1.3.6.1.5.5.7.48.1.1";
+/// Extended key usage of the certificates the issuer delegates the signature of OCSP responses to.
+const OCSP_SIGNING: &str = "1.3.6.1.5.5.7.3.9";
+/// Seconds a response can be produced ahead of the clock of the gateway.
+const MAX_CLOCK_SKEW: i64 = 300;
+
+/// CertStatus tags of a single response.
+const GOOD: u8 = 0x80;
+const REVOKED: u8 = 0xa1;
+const UNKNOWN: u8 = 0x82;
+
+/// Reasons an OCSP response can not be used.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum OcspError {
+    Malformed(DerError),
+    /// The responder did not answer successfully, with the status of the response.
+    Unsuccessful(u8),
+    UnsupportedResponseType(String),
+    /// The response does not cover the certificate.
+    MissingCertificate,
+    /// The response is not signed by the issuer, nor by a responder the issuer delegated to.
+    InvalidSignature,
+    /// The response is produced after the current time, beyond the tolerated clock skew.
+    NotYetValid,
+    /// The response is past its next update or, without one, older than the maximum age.
+    Outdated,
+}
+
+impl fmt::Display for OcspError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OcspError::Malformed(err) => write!(f, "malformed response: {}", err),
+            OcspError::Unsuccessful(status) => write!(f, "unsuccessful response status {}", status),
+            OcspError::UnsupportedResponseType(oid) => write!(f, "unsupported response type {}", oid),
+            OcspError::MissingCertificate => write!(f, "no response for the certificate"),
+            OcspError::InvalidSignature => write!(f, "response not signed by the issuer or a delegated responder"),
+            OcspError::NotYetValid => write!(f, "response produced in the future"),
+            OcspError::Outdated => write!(f, "outdated response"),
+        }
+    }
+}
+
+impl From<DerError> for OcspError {
+    fn from(err: DerError) -> OcspError {
+        OcspError::Malformed(err)
+    }
+}
+
+/// Identifies a certificate to an OCSP responder, with SHA-1 hashes as most responders expect.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CertId {
+    issuer_name_hash: Vec<u8>,
+    issuer_key_hash: Vec<u8>,
+    /// Contents of the serial number INTEGER.
+    serial_number: Vec<u8>,
+}
+
+impl CertId {
+    /// Builds the ID of a certificate from the certificate of its issuer.
+    pub fn new(certificate: &Certificate, issuer: &Certificate) -> Result<CertId, DerError> {
+        let mut public_key_info = Reader::new(Reader::new(&issuer.subject_public_key_info).read_tag(SEQUENCE)?);
+        public_key_info.read_tag(SEQUENCE)?;
+        // The key is hashed without the byte counting the unused bits of the bit string
+        let public_key = match public_key_info.read_tag(BIT_STRING)? {
+            [_, key @ ..] => key,
+            [] => return Err(DerError::InvalidLength),
+        };
+        Ok(CertId {
+            issuer_name_hash: Sha1::digest(&certificate.issuer_der).to_vec(),
+            issuer_key_hash: Sha1::digest(public_key).to_vec(),
+            serial_number: serial_number_integer(&certificate.serial_number),
+        })
+    }
+
+    /// Key of the certificate in the cache.
+    pub fn key(&self) -> String {
+        format!(
+            "{}:{}:{}",
+            encoding::hex(&self.issuer_name_hash),
+            encoding::hex(&self.issuer_key_hash),
+            encoding::hex(&self.serial_number)
+        )
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let hash_algorithm = der::encode(
+            SEQUENCE,
+            &[der::encode(OBJECT_IDENTIFIER, SHA1_OID), der::encode(NULL, &[])].concat(),
+        );
+        der::encode(
+            SEQUENCE,
+            &[
+                hash_algorithm,
+                der::encode(OCTET_STRING, &self.issuer_name_hash),
+                der::encode(OCTET_STRING, &self.issuer_key_hash),
+                der::encode(INTEGER, &self.serial_number),
+            ]
+            .concat(),
+        )
+    }
+
+    /// Tells whether an encoded CertID identifies this certificate, whatever the hash algorithm parameters.
+    fn matches(&self, cert_id: &[u8]) -> Result<bool, DerError> {
+        let mut cert_id = Reader::new(cert_id);
+        let mut hash_algorithm = Reader::new(cert_id.read_tag(SEQUENCE)?);
+        Ok(hash_algorithm.read_tag(OBJECT_IDENTIFIER)? == SHA1_OID
+            && cert_id.read_tag(OCTET_STRING)? == self.issuer_name_hash.as_slice()
+            && cert_id.read_tag(OCTET_STRING)? == self.issuer_key_hash.as_slice()
+            && cert_id.read_tag(INTEGER)? == self.serial_number.as_slice())
+    }
+}
+
+/// Writes the serial number back as the contents of an INTEGER, with the leading zero byte keeping it positive.
+fn serial_number_integer(serial_number: &str) -> Vec<u8> {
+    let mut bytes = encoding::from_hex(serial_number).unwrap_or_default();
+    if bytes.first().is_none_or(|byte| *byte & 0x80 != 0) {
+        bytes.insert(0, 0);
+    }
+    bytes
+}
+
+/// Encodes an OCSP request for a single certificate, without nonce so responders can serve cached responses.
+pub fn request(cert_id: &CertId) -> Vec<u8> {
+    let request = der::encode(SEQUENCE, &cert_id.encode());
+    let request_list = der::encode(SEQUENCE, &request);
+    let tbs_request = der::encode(SEQUENCE, &request_list);
+    der::encode(SEQUENCE, &tbs_request)
+}
+
+/// Status of a certificate given by a responder.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SingleResponse {
+    pub status: RevocationStatus,
+    pub this_update: i64,
+    pub next_update: Option<i64>,
+}
+
+/// Decodes the status of a certificate from an OCSP response. The response must be signed by the issuer of the
+/// certificate or by a responder it delegated to, and be current: responses without next update are current for
+/// `max_age` seconds.
+pub fn parse_response(
+    response: &[u8],
+    cert_id: &CertId,
+    issuer: &Certificate,
+    now: i64,
+    max_age: i64,
+) -> Result<SingleResponse, OcspError> {
+    let mut ocsp_response = Reader::new(Reader::new(response).read_tag(SEQUENCE)?);
+    match ocsp_response.read_tag(ENUMERATED)? {
+        [0] => {}
+        [status] => return Err(OcspError::Unsuccessful(*status)),
+        _ => return Err(DerError::InvalidLength.into()),
+    }
+
+    let response_bytes = ocsp_response.read_tag(CONTEXT_0)?;
+    let mut response_bytes = Reader::new(Reader::new(response_bytes).read_tag(SEQUENCE)?);
+    let response_type = der::object_identifier(response_bytes.read_tag(OBJECT_IDENTIFIER)?)?;
+    if response_type != BASIC_RESPONSE {
+        return Err(OcspError::UnsupportedResponseType(response_type));
+    }
+
+    let mut basic_response = Reader::new(Reader::new(response_bytes.read_tag(OCTET_STRING)?).read_tag(SEQUENCE)?);
+    let signed = Signed::read(&mut basic_response)?;
+    let certificates = match basic_response.read_optional(CONTEXT_0)? {
+        Some(certificates) => Reader::new(certificates).read_tag(SEQUENCE)?,
+        None => &[],
+    };
+    verify_signature(&signed, certificates, issuer, now)?;
+
+    let mut response_data = Reader::new(signed.tbs);
+    response_data.read_optional(CONTEXT_0)?;
+    // Responder ID, by name or by key hash
+    response_data.read()?;
+    response_data.read_tag(GENERALIZED_TIME)?;
+
+    let mut responses = Reader::new(response_data.read_tag(SEQUENCE)?);
+    while !responses.is_empty() {
+        let mut single_response = Reader::new(responses.read_tag(SEQUENCE)?);
+        if !cert_id.matches(single_response.read_tag(SEQUENCE)?)? {
+            continue;
+        }
+        let status = match single_response.read()?.tag {
+            GOOD => RevocationStatus::Good,
+            REVOKED => RevocationStatus::Revoked,
+            UNKNOWN => RevocationStatus::Unknown,
+            found => return Err(DerError::UnexpectedTag { expected: GOOD, found }.into()),
+        };
+        let this_update = single_response.read_time()?;
+        let next_update = match single_response.read_optional(CONTEXT_0)? {
+            Some(next_update) => Some(Reader::new(next_update).read_time()?),
+            None => None,
+        };
+        if this_update > now + MAX_CLOCK_SKEW {
+            return Err(OcspError::NotYetValid);
+        }
+        if next_update.unwrap_or(this_update + max_age) < now {
+            return Err(OcspError::Outdated);
+        }
+        return Ok(SingleResponse {
+            status,
+            this_update,
+            next_update,
+        });
+    }
+    Err(OcspError::MissingCertificate)
+}
+
+/// Checks that a response is signed by the issuer, or by one of the certificates of the response that the issuer
+/// issued for signing OCSP responses and that is valid.
+fn verify_signature(signed: &Signed, certificates: &[u8], issuer: &Certificate, now: i64) -> Result<(), OcspError> {
+    if signed.verify(&issuer.subject_public_key_info).is_ok() {
+        return Ok(());
+    }
+    let mut certificates = Reader::new(certificates);
+    while !certificates.is_empty() {
+        let certificate = certificates.read()?;
+        let responder = Certificate::parse(&der::encode(certificate.tag, certificate.value))?;
+        let delegated = responder.issuer == issuer.subject
+            && (responder.validity.not_before..=responder.validity.not_after).contains(&now)
+            && responder
+                .extended_key_usages()?
+                .is_some_and(|usages| usages.iter().any(|usage| usage == OCSP_SIGNING))
+            && Signed::parse(&responder.der)?
+                .verify(&issuer.subject_public_key_info)
+                .is_ok();
+        if delegated && signed.verify(&responder.subject_public_key_info).is_ok() {
+            return Ok(());
+        }
+    }
+    Err(OcspError::InvalidSignature)
+}
+
+/// Statuses given by the responder, by certificate, until they expire.
+#[derive(Debug, Default)]
+pub struct OcspCache {
+    entries: HashMap<String, (RevocationStatus, i64)>,
+}
+
+impl OcspCache {
+    /// Returns the cached status of a certificate, unless it expired.
+    pub fn get(&self, key: &str, now: i64) -> Option<RevocationStatus> {
+        match self.entries.get(key) {
+            Some((status, expires)) if now < *expires => Some(*status),
+            _ => None,
+        }
+    }
+
+    /// Caches a status until the given time, dropping the entries that expired.
+    pub fn insert(&mut self, key: String, status: RevocationStatus, expires: i64, now: i64) {
+        self.entries.retain(|_, (_, expires)| now < *expires);
+        self.entries.insert(key, (status, expires));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CA_PEM: &str = include_str!("../tests/resources/ca.pem");
+    const CLIENT_PEM: &str = include_str!("../tests/resources/client.pem");
+    const REQUEST: &[u8] = include_bytes!("../tests/resources/ocsp-request.der");
+    const GOOD_RESPONSE: &[u8] = include_bytes!("../tests/resources/ocsp-good.der");
+    const REVOKED_RESPONSE: &[u8] = include_bytes!("../tests/resources/ocsp-revoked.der");
+    const DELEGATED_RESPONSE: &[u8] = include_bytes!("../tests/resources/ocsp-delegated.der");
+    const DELEGATED_P384_RESPONSE: &[u8] = include_bytes!("../tests/resources/ocsp-delegated-p384.der");
+    const UNAUTHORIZED_RESPONSE: &[u8] = include_bytes!("../tests/resources/ocsp-unauthorized.der");
+    /// Shortly after the test responses were produced.
+    const NOW: i64 = 1_792_047_600;
+    const MAX_AGE: i64 = 3600;
+
+    fn certificate(pem: &str) -> Certificate {
+        Certificate::parse(&encoding::certificate_der(pem).unwrap().unwrap()).unwrap()
+    }
+
+    fn client_cert_id() -> CertId {
+        CertId::new(&certificate(CLIENT_PEM), &certificate(CA_PEM)).unwrap()
+    }
+
+    #[test]
+    fn encodes_request_like_openssl() {
+        assert_eq!(request(&client_cert_id()), REQUEST);
+    }
+
+    #[test]
+    fn writes_serial_numbers_as_positive_integers() {
+        assert_eq!(serial_number_integer("1a2b"), vec![0x1a, 0x2b]);
+        assert_eq!(serial_number_integer("ff01"), vec![0x00, 0xff, 0x01]);
+        assert_eq!(serial_number_integer(""), vec![0x00]);
+    }
+
+    #[test]
+    fn parses_responses() {
+        let cert_id = client_cert_id();
+        let issuer = certificate(CA_PEM);
+
+        let good = parse_response(GOOD_RESPONSE, &cert_id, &issuer, NOW, MAX_AGE).unwrap();
+        assert_eq!(good.status, RevocationStatus::Good);
+        assert_eq!(good.next_update, None);
+
+        let revoked = parse_response(REVOKED_RESPONSE, &cert_id, &issuer, NOW, MAX_AGE).unwrap();
+        assert_eq!(revoked.status, RevocationStatus::Revoked);
+        assert!(revoked.next_update.unwrap() > revoked.this_update);
+
+        let other = CertId {
+            serial_number: vec![0x01],
+            ..cert_id
+        };
+        assert_eq!(
+            parse_response(GOOD_RESPONSE, &other, &issuer, NOW, MAX_AGE),
+            Err(OcspError::MissingCertificate)
+        );
+        // Unauthorized
+        assert_eq!(
+            parse_response(&[0x30, 0x03, 0x0a, 0x01, 0x06], &other, &issuer, NOW, MAX_AGE),
+            Err(OcspError::Unsuccessful(6))
+        );
+    }
+
+    #[test]
+    fn rejects_tampered_responses() {
+        let cert_id = client_cert_id();
+        let issuer = certificate(CA_PEM);
+
+        // Move the revocation time from 2025 to 2125
+        let mut tampered = REVOKED_RESPONSE.to_vec();
+        let position = tampered
+            .windows(4)
+            .position(|window| window == [REVOKED, 0x11, GENERALIZED_TIME, 0x0f])
+            .unwrap();
+        tampered[position + 5] = b'1';
+        assert_eq!(
+            parse_response(&tampered, &cert_id, &issuer, NOW, MAX_AGE),
+            Err(OcspError::InvalidSignature)
+        );
+
+        // Signed by another key than the key of the issuer
+        assert_eq!(
+            parse_response(GOOD_RESPONSE, &cert_id, &certificate(CLIENT_PEM), NOW, MAX_AGE),
+            Err(OcspError::InvalidSignature)
+        );
+    }
+
+    #[test]
+    fn accepts_delegated_responders_only() {
+        let cert_id = client_cert_id();
+        let issuer = certificate(CA_PEM);
+
+        let delegated = parse_response(DELEGATED_RESPONSE, &cert_id, &issuer, NOW, MAX_AGE).unwrap();
+        assert_eq!(delegated.status, RevocationStatus::Good);
+        // The certificate of the gateway is issued by the CA, but not for OCSP
+        assert_eq!(
+            parse_response(UNAUTHORIZED_RESPONSE, &cert_id, &issuer, NOW, MAX_AGE),
+            Err(OcspError::InvalidSignature)
+        );
+    }
+
+    #[test]
+    fn accepts_delegated_responders_with_p384_keys() {
+        let cert_id = client_cert_id();
+        let issuer = certificate(CA_PEM);
+        // Shortly after the response was produced, with ECDSA P-384 and SHA-384
+        let now = 1_792_049_200;
+
+        let delegated = parse_response(DELEGATED_P384_RESPONSE, &cert_id, &issuer, now, MAX_AGE).unwrap();
+        assert_eq!(delegated.status, RevocationStatus::Good);
+        assert_eq!(
+            parse_response(DELEGATED_P384_RESPONSE, &cert_id, &certificate(CLIENT_PEM), now, MAX_AGE),
+            Err(OcspError::InvalidSignature)
+        );
+    }
+
+    #[test]
+    fn rejects_stale_responses() {
+        let cert_id = client_cert_id();
+        let issuer = certificate(CA_PEM);
+        let this_update = parse_response(GOOD_RESPONSE, &cert_id, &issuer, NOW, MAX_AGE)
+            .unwrap()
+            .this_update;
+
+        // Without next update, responses are current for the maximum age
+        assert!(parse_response(GOOD_RESPONSE, &cert_id, &issuer, this_update + MAX_AGE, MAX_AGE).is_ok());
+        assert_eq!(
+            parse_response(GOOD_RESPONSE, &cert_id, &issuer, this_update + MAX_AGE + 1, MAX_AGE),
+            Err(OcspError::Outdated)
+        );
+        assert_eq!(
+            parse_response(
+                GOOD_RESPONSE,
+                &cert_id,
+                &issuer,
+                this_update - MAX_CLOCK_SKEW - 1,
+                MAX_AGE
+            ),
+            Err(OcspError::NotYetValid)
+        );
+        // Past the next update
+        assert_eq!(
+            parse_response(REVOKED_RESPONSE, &cert_id, &issuer, 5_000_000_000, MAX_AGE),
+            Err(OcspError::Outdated)
+        );
+    }
+
+    #[test]
+    fn expires_cached_statuses() {
+        let mut cache = OcspCache::default();
+        cache.insert("a".to_string(), RevocationStatus::Good, 100, 0);
+
+        assert_eq!(cache.get("a", 99), Some(RevocationStatus::Good));
+        assert_eq!(cache.get("a", 100), None);
+
+        cache.insert("b".to_string(), RevocationStatus::Revoked, 200, 150);
+        assert_eq!(cache.get("a", 0), None);
+        assert_eq!(cache.get("b", 150), Some(RevocationStatus::Revoked));
+    }
+}
@@ -0,0 +1,129 @@
+// Copyright 2023 Salesforce, Inc. All rights reserved.
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use std::fmt;
+
+/// A block of a PEM document, such as a certificate.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PemBlock {
+    /// Label of the block, `CERTIFICATE` for `-----BEGIN CERTIFICATE-----`.
+    pub label: String,
+    /// Base64 encoded contents, without line breaks.
+    pub base64: String,
+}
+
+/// Reasons a PEM document can not be parsed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PemError {
+    MissingEnd(String),
+    InvalidBase64(String),
+}
+
+impl fmt::Display for PemError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PemError::MissingEnd(label) => write!(f, "missing end of '{}' block", label),
+            PemError::InvalidBase64(label) => write!(f, "invalid base64 contents in '{}' block", label),
+        }
+    }
+}
+
+impl PemBlock {
+    /// Returns the DER encoded contents of the block.
+    pub fn der(&self) -> Result<Vec<u8>, PemError> {
+        STANDARD
+            .decode(&self.base64)
+            .map_err(|_| PemError::InvalidBase64(self.label.clone()))
+    }
+
+    /// Writes the block back as PEM, with 64 characters lines.
+    pub fn to_pem(&self) -> String {
+        let mut pem = format!("-----BEGIN {}-----\n", self.label);
+        for line in self.base64.as_bytes().chunks(64) {
+            pem.push_str(&String::from_utf8_lossy(line));
+            pem.push('\n');
+        }
+        pem.push_str(&format!("-----END {}-----\n", self.label));
+        pem
+    }
+}
+
+/// Parses all the blocks of a PEM document, ignoring the text between them.
+pub fn parse(document: &str) -> Result<Vec<PemBlock>, PemError> {
+    let mut blocks = Vec::new();
+    let mut lines = document.lines().map(str::trim);
+
+    while let Some(line) = lines.next() {
+        let label = match line.strip_prefix("-----BEGIN ").and_then(|rest| rest.strip_suffix("-----")) {
+            Some(label) => label.to_string(),
+            None => continue,
+        };
+        let end = format!("-----END {}-----", label);
+
+        let mut base64 = String::new();
+        loop {
+            match lines.next() {
+                Some(line) if line == end => break,
+                // Skip RFC 1421 headers, such as Proc-Type.
+                Some(line) if line.contains(':') => continue,
+                Some(line) => base64.push_str(line),
+                None => return Err(PemError::MissingEnd(label)),
+            }
+        }
+        blocks.push(PemBlock { label, base64 });
+    }
+
+    Ok(blocks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOCUMENT: &str = "subject=CN=Joker
+-----BEGIN CERTIFICATE-----
+AQID
+BAU=
+-----END CERTIFICATE-----
+-----BEGIN CERTIFICATE-----
+/w==
+-----END CERTIFICATE-----
+";
+
+    #[test]
+    fn parses_blocks() {
+        let blocks = parse(DOCUMENT).unwrap();
+
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[0].label, "CERTIFICATE");
+        assert_eq!(blocks[0].base64, "AQIDBAU=");
+        assert_eq!(blocks[1].base64, "/w==");
+        assert_eq!(blocks[0].der().unwrap(), vec![1, 2, 3, 4, 5]);
+        assert_eq!(blocks[1].der().unwrap(), vec![255]);
+    }
+
+    #[test]
+    fn writes_blocks() {
+        let block = PemBlock {
+            label: "CERTIFICATE".to_string(),
+            base64: "A".repeat(70),
+        };
+
+        assert_eq!(
+            block.to_pem(),
+            format!(
+                "-----BEGIN CERTIFICATE-----\n{}\n{}\n-----END CERTIFICATE-----\n",
+                "A".repeat(64),
+                "A".repeat(6)
+            )
+        );
+    }
+
+    #[test]
+    fn rejects_unterminated_blocks() {
+        assert_eq!(
+            parse("-----BEGIN CERTIFICATE-----\nAQID\n"),
+            Err(PemError::MissingEnd("CERTIFICATE".to_string()))
+        );
+    }
+}
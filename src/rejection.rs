@@ -0,0 +1,59 @@
+// Copyright 2023 Salesforce, Inc. All rights reserved.
+use pdk::hl::Response;
+use serde_json::json;
+
+const PROBLEM_CONTENT_TYPE: &str = "application/problem+json";
+/// Content type of configured bodies when the configuration does not set one.
+pub const BODY_CONTENT_TYPE: &str = "text/plain";
+
+/// Response returned when the policy denies a request.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rejection {
+    pub status: u32,
+    /// Body sent instead of the generated problem details document.
+    pub body: Option<String>,
+    /// Content type of the configured body.
+    pub content_type: String,
+}
+
+impl Rejection {
+    pub fn new(status: u32) -> Rejection {
+        Rejection {
+            status,
+            body: None,
+            content_type: BODY_CONTENT_TYPE.to_string(),
+        }
+    }
+
+    /// Builds the response, an RFC 9457 problem details document unless a body is configured.
+    pub fn response(&self, detail: &str) -> Response {
+        let (content_type, body) = match &self.body {
+            Some(body) => (self.content_type.as_str(), body.clone()),
+            None => (
+                PROBLEM_CONTENT_TYPE,
+                json!({
+                    "type": "about:blank",
+                    "title": title(self.status),
+                    "status": self.status,
+                    "detail": detail,
+                })
+                .to_string(),
+            ),
+        };
+
+        Response::new(self.status)
+            .with_headers(vec![("content-type".to_string(), content_type.to_string())])
+            .with_body(body)
+    }
+}
+
+fn title(status: u32) -> &'static str {
+    match status {
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        495 => "SSL Certificate Error",
+        496 => "SSL Certificate Required",
+        _ => "Request Rejected",
+    }
+}
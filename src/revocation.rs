@@ -0,0 +1,217 @@
+// Copyright 2023 Salesforce, Inc. All rights reserved.
+use crate::der::{DerError, Reader, GENERALIZED_TIME, INTEGER, SEQUENCE, UTC_TIME};
+use crate::pem;
+use crate::signature::Signed;
+use crate::subject::format_name;
+use crate::x509::{self, Certificate};
+use std::collections::{HashMap, HashSet};
+
+const CRL_LABEL: &str = "X509 CRL";
+
+/// Revocation status of a certificate.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RevocationStatus {
+    Good,
+    Revoked,
+    /// No up to date revocation information covers the certificate.
+    Unknown,
+}
+
+/// Certificate revocation list decoded from DER.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Crl {
+    /// Issuer name in its string representation, as written by `format_name`.
+    pub issuer: String,
+    pub this_update: i64,
+    pub next_update: Option<i64>,
+    /// Serial numbers of the revoked certificates, as lowercase hex.
+    pub revoked: HashSet<String>,
+}
+
+impl Crl {
+    /// Decodes a DER encoded CRL, once its signature is verified with the key of its issuer, looked up by name
+    /// among the given certificates.
+    pub fn parse(crl_der: &[u8], issuers: &[Certificate]) -> Result<Crl, String> {
+        let signed = Signed::parse(crl_der).map_err(|err| format!("Malformed CRL: {}", err))?;
+        let crl = Crl::parse_tbs(signed.tbs).map_err(|err| format!("Malformed CRL: {}", err))?;
+        let issuer = issuers
+            .iter()
+            .find(|issuer| format_name(issuer.subject.iter().rev()) == crl.issuer)
+            .ok_or_else(|| format!("No issuer certificate for the CRL of '{}'", crl.issuer))?;
+        signed
+            .verify(&issuer.subject_public_key_info)
+            .map_err(|err| format!("Invalid CRL of '{}': {}", crl.issuer, err))?;
+        Ok(crl)
+    }
+
+    fn parse_tbs(tbs_cert_list: &[u8]) -> Result<Crl, DerError> {
+        let mut tbs_cert_list = Reader::new(tbs_cert_list);
+
+        tbs_cert_list.read_optional(INTEGER)?;
+        // Signature algorithm, repeated outside of the signed part
+        tbs_cert_list.read_tag(SEQUENCE)?;
+        let issuer = x509::parse_name(tbs_cert_list.read_tag(SEQUENCE)?)?;
+        let this_update = tbs_cert_list.read_time()?;
+        let next_update = match tbs_cert_list.peek_tag() {
+            Some(UTC_TIME) | Some(GENERALIZED_TIME) => Some(tbs_cert_list.read_time()?),
+            _ => None,
+        };
+
+        // Entry and CRL extensions are skipped
+        let mut revoked = HashSet::new();
+        if let Some(entries) = tbs_cert_list.read_optional(SEQUENCE)? {
+            let mut entries = Reader::new(entries);
+            while !entries.is_empty() {
+                let mut entry = Reader::new(entries.read_tag(SEQUENCE)?);
+                revoked.insert(x509::serial_number(entry.read_tag(INTEGER)?));
+            }
+        }
+
+        Ok(Crl {
+            issuer: format_name(issuer.iter().rev()),
+            this_update,
+            next_update,
+            revoked,
+        })
+    }
+}
+
+/// Decodes the CRLs of a PEM document or, when it is not PEM, a single DER encoded CRL. Every CRL must be signed
+/// by one of the issuers.
+pub fn parse_crls(document: &[u8], issuers: &[Certificate]) -> Result<Vec<Crl>, String> {
+    if document.first() == Some(&SEQUENCE) {
+        return Crl::parse(document, issuers).map(|crl| vec![crl]);
+    }
+    let document = std::str::from_utf8(document).map_err(|_| "CRL is neither PEM nor DER".to_string())?;
+    let mut crls = Vec::new();
+    for block in pem::parse(document).map_err(|err| format!("Malformed CRL PEM: {}", err))? {
+        if block.label != CRL_LABEL {
+            continue;
+        }
+        let crl_der = block.der().map_err(|err| format!("Malformed CRL PEM: {}", err))?;
+        crls.push(Crl::parse(&crl_der, issuers)?);
+    }
+    Ok(crls)
+}
+
+/// The CRLs known to the policy, indexed by issuer.
+#[derive(Debug, Default)]
+pub struct CrlStore {
+    configured: Vec<Crl>,
+    by_issuer: HashMap<String, Crl>,
+}
+
+impl CrlStore {
+    pub fn new(configured: Vec<Crl>) -> CrlStore {
+        let mut store = CrlStore {
+            configured,
+            by_issuer: HashMap::new(),
+        };
+        store.update(Vec::new());
+        store
+    }
+
+    /// Replaces the fetched CRLs. When several CRLs have the same issuer, the most recent one is kept.
+    pub fn update(&mut self, fetched: Vec<Crl>) {
+        let mut by_issuer: HashMap<String, Crl> = HashMap::new();
+        for crl in self.configured.iter().cloned().chain(fetched) {
+            match by_issuer.get(&crl.issuer) {
+                Some(known) if known.this_update >= crl.this_update => {}
+                _ => {
+                    by_issuer.insert(crl.issuer.clone(), crl);
+                }
+            }
+        }
+        self.by_issuer = by_issuer;
+    }
+
+    /// Looks up the status of a certificate. Certificates of issuers without CRL, or whose CRL is past its
+    /// next update, have an unknown status.
+    pub fn status(&self, issuer: &str, serial_number: &str, now: i64) -> RevocationStatus {
+        match self.by_issuer.get(issuer) {
+            Some(crl) if crl.revoked.contains(serial_number) => RevocationStatus::Revoked,
+            Some(crl) if crl.next_update.is_some_and(|next_update| next_update < now) => RevocationStatus::Unknown,
+            Some(_) => RevocationStatus::Good,
+            None => RevocationStatus::Unknown,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CA_PEM: &str = include_str!("../tests/resources/ca.pem");
+    const CLIENT_PEM: &str = include_str!("../tests/resources/client.pem");
+    const CRL_PEM: &str = include_str!("../tests/resources/crl.pem");
+    const ISSUER: &str = "CN=Internal CA 2,O=ACME,C=US";
+
+    fn certificates(pem: &str) -> Vec<Certificate> {
+        vec![Certificate::parse(&crate::encoding::certificate_der(pem).unwrap().unwrap()).unwrap()]
+    }
+
+    #[test]
+    fn parses_crl() {
+        let issuers = certificates(CA_PEM);
+        let crls = parse_crls(CRL_PEM.as_bytes(), &issuers).unwrap();
+
+        assert_eq!(crls.len(), 1);
+        assert_eq!(crls[0].issuer, ISSUER);
+        assert_eq!(x509::format_time(crls[0].this_update), "2025-01-01T00:00:00Z");
+        assert_eq!(
+            crls[0].next_update.map(x509::format_time).as_deref(),
+            Some("2125-01-01T00:00:00Z")
+        );
+        assert_eq!(
+            crls[0].revoked,
+            vec!["0102".to_string(), "1a2b3c4d5e6f".to_string()]
+                .into_iter()
+                .collect()
+        );
+
+        let crl_der = pem::parse(CRL_PEM).unwrap()[0].der().unwrap();
+        assert_eq!(parse_crls(&crl_der, &issuers).unwrap(), crls);
+        assert!(parse_crls(&crl_der[..crl_der.len() - 1], &issuers).is_err());
+    }
+
+    #[test]
+    fn rejects_crls_not_signed_by_their_issuer() {
+        let issuers = certificates(CA_PEM);
+        let crl_der = pem::parse(CRL_PEM).unwrap()[0].der().unwrap();
+
+        // Un-revoke the client certificate by changing its serial number in the list
+        let mut tampered = crl_der.clone();
+        let position = tampered
+            .windows(6)
+            .position(|window| window == [0x1a, 0x2b, 0x3c, 0x4d, 0x5e, 0x6f])
+            .unwrap();
+        tampered[position] = 0x1b;
+        assert_eq!(
+            parse_crls(&tampered, &issuers),
+            Err(format!("Invalid CRL of '{}': signature does not match", ISSUER))
+        );
+
+        // The client certificate does not have the name of the issuer of the CRL
+        assert_eq!(
+            parse_crls(&crl_der, &certificates(CLIENT_PEM)),
+            Err(format!("No issuer certificate for the CRL of '{}'", ISSUER))
+        );
+    }
+
+    #[test]
+    fn looks_up_revocation_status() {
+        let mut store = CrlStore::new(Vec::new());
+        assert_eq!(store.status(ISSUER, "1a2b3c4d5e6f", 0), RevocationStatus::Unknown);
+
+        store.update(parse_crls(CRL_PEM.as_bytes(), &certificates(CA_PEM)).unwrap());
+        assert_eq!(store.status(ISSUER, "1a2b3c4d5e6f", 0), RevocationStatus::Revoked);
+        assert_eq!(store.status(ISSUER, "1a2b3c", 0), RevocationStatus::Good);
+        assert_eq!(store.status("CN=Other CA", "1a2b3c", 0), RevocationStatus::Unknown);
+        // After the next update, only revoked certificates are known
+        assert_eq!(
+            store.status(ISSUER, "1a2b3c4d5e6f", 5_000_000_000),
+            RevocationStatus::Revoked
+        );
+        assert_eq!(store.status(ISSUER, "1a2b3c", 5_000_000_000), RevocationStatus::Unknown);
+    }
+}
@@ -0,0 +1,1434 @@
+// Copyright 2023 Salesforce, Inc. All rights reserved.
+use crate::authorization::{Authorization, Condition, Effect, Matcher, Rule, RuleMatching};
+use crate::encoding::{self, CertificateEncoding};
+use crate::generated::config::Config;
+use crate::jwt::SigningKey;
+use crate::rejection::{Rejection, BODY_CONTENT_TYPE};
+use crate::revocation::{self, Crl};
+use crate::x509::{Certificate, EXTENDED_KEY_USAGES};
+use crate::xfcc::{Detail, ForwardMode};
+use anyhow::{anyhow, Result};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use pdk::hl::Service;
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+const DEFAULT_HEADER_PREFIX: &str = "X-Peer-";
+
+/// Attributes forwarded when the configuration does not list them, the headers set by the first version.
+const DEFAULT_ATTRIBUTES: [Attribute; 11] = [
+    Attribute::Name,
+    Attribute::Email,
+    Attribute::Organization,
+    Attribute::OrganizationUnit,
+    Attribute::Country,
+    Attribute::Locality,
+    Attribute::State,
+    Attribute::SanDns,
+    Attribute::SanIp,
+    Attribute::SanEmail,
+    Attribute::SanUri,
+];
+
+/// Certificate attributes the policy knows how to extract and forward.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Attribute {
+    Name,
+    Email,
+    Organization,
+    OrganizationUnit,
+    Country,
+    Locality,
+    State,
+    Surname,
+    GivenName,
+    Initials,
+    GenerationQualifier,
+    Pseudonym,
+    Title,
+    UserId,
+    SerialNumber,
+    DnQualifier,
+    OrganizationIdentifier,
+    BusinessCategory,
+    DomainComponent,
+    Street,
+    PostalCode,
+    SanDns,
+    SanIp,
+    SanEmail,
+    SanUri,
+    SpiffeId,
+    SpiffeTrustDomain,
+    SpiffePath,
+    FingerprintSha256,
+    FingerprintSha1,
+    CertificateSerial,
+    Issuer,
+    IssuerName,
+    IssuerEmail,
+    IssuerOrganization,
+    IssuerOrganizationUnit,
+    IssuerOrganizationIdentifier,
+    IssuerDomainComponent,
+    IssuerSerialNumber,
+    IssuerLocality,
+    IssuerState,
+    IssuerCountry,
+    NotBefore,
+    NotAfter,
+    DaysUntilExpiry,
+    ExtendedKeyUsage,
+}
+
+impl Attribute {
+    pub const ALL: [Attribute; 46] = [
+        Attribute::Name,
+        Attribute::Email,
+        Attribute::Organization,
+        Attribute::OrganizationUnit,
+        Attribute::Country,
+        Attribute::Locality,
+        Attribute::State,
+        Attribute::Surname,
+        Attribute::GivenName,
+        Attribute::Initials,
+        Attribute::GenerationQualifier,
+        Attribute::Pseudonym,
+        Attribute::Title,
+        Attribute::UserId,
+        Attribute::SerialNumber,
+        Attribute::DnQualifier,
+        Attribute::OrganizationIdentifier,
+        Attribute::BusinessCategory,
+        Attribute::DomainComponent,
+        Attribute::Street,
+        Attribute::PostalCode,
+        Attribute::SanDns,
+        Attribute::SanIp,
+        Attribute::SanEmail,
+        Attribute::SanUri,
+        Attribute::SpiffeId,
+        Attribute::SpiffeTrustDomain,
+        Attribute::SpiffePath,
+        Attribute::FingerprintSha256,
+        Attribute::FingerprintSha1,
+        Attribute::CertificateSerial,
+        Attribute::Issuer,
+        Attribute::IssuerName,
+        Attribute::IssuerEmail,
+        Attribute::IssuerOrganization,
+        Attribute::IssuerOrganizationUnit,
+        Attribute::IssuerOrganizationIdentifier,
+        Attribute::IssuerDomainComponent,
+        Attribute::IssuerSerialNumber,
+        Attribute::IssuerLocality,
+        Attribute::IssuerState,
+        Attribute::IssuerCountry,
+        Attribute::NotBefore,
+        Attribute::NotAfter,
+        Attribute::DaysUntilExpiry,
+        Attribute::ExtendedKeyUsage,
+    ];
+
+    /// Parses the attribute from its name in the policy configuration.
+    pub fn from_key(key: &str) -> Option<Attribute> {
+        Attribute::ALL.iter().copied().find(|attribute| attribute.key() == key)
+    }
+
+    /// Name of the attribute in the policy configuration.
+    pub fn key(self) -> &'static str {
+        match self {
+            Attribute::Name => "name",
+            Attribute::Email => "email",
+            Attribute::Organization => "organization",
+            Attribute::OrganizationUnit => "organizationUnit",
+            Attribute::Country => "country",
+            Attribute::Locality => "locality",
+            Attribute::State => "state",
+            Attribute::Surname => "surname",
+            Attribute::GivenName => "givenName",
+            Attribute::Initials => "initials",
+            Attribute::GenerationQualifier => "generationQualifier",
+            Attribute::Pseudonym => "pseudonym",
+            Attribute::Title => "title",
+            Attribute::UserId => "userId",
+            Attribute::SerialNumber => "serialNumber",
+            Attribute::DnQualifier => "dnQualifier",
+            Attribute::OrganizationIdentifier => "organizationIdentifier",
+            Attribute::BusinessCategory => "businessCategory",
+            Attribute::DomainComponent => "domainComponent",
+            Attribute::Street => "street",
+            Attribute::PostalCode => "postalCode",
+            Attribute::SanDns => "sanDns",
+            Attribute::SanIp => "sanIp",
+            Attribute::SanEmail => "sanEmail",
+            Attribute::SanUri => "sanUri",
+            Attribute::SpiffeId => "spiffeId",
+            Attribute::SpiffeTrustDomain => "spiffeTrustDomain",
+            Attribute::SpiffePath => "spiffePath",
+            Attribute::FingerprintSha256 => "fingerprintSha256",
+            Attribute::FingerprintSha1 => "fingerprintSha1",
+            Attribute::CertificateSerial => "certificateSerial",
+            Attribute::Issuer => "issuer",
+            Attribute::IssuerName => "issuerName",
+            Attribute::IssuerEmail => "issuerEmail",
+            Attribute::IssuerOrganization => "issuerOrganization",
+            Attribute::IssuerOrganizationUnit => "issuerOrganizationUnit",
+            Attribute::IssuerOrganizationIdentifier => "issuerOrganizationIdentifier",
+            Attribute::IssuerDomainComponent => "issuerDomainComponent",
+            Attribute::IssuerSerialNumber => "issuerSerialNumber",
+            Attribute::IssuerLocality => "issuerLocality",
+            Attribute::IssuerState => "issuerState",
+            Attribute::IssuerCountry => "issuerCountry",
+            Attribute::NotBefore => "notBefore",
+            Attribute::NotAfter => "notAfter",
+            Attribute::DaysUntilExpiry => "daysUntilExpiry",
+            Attribute::ExtendedKeyUsage => "extendedKeyUsage",
+        }
+    }
+
+    /// Human readable description used in error messages.
+    pub fn description(self) -> &'static str {
+        match self {
+            Attribute::Name => "Common name",
+            Attribute::Email => "Email address",
+            Attribute::Organization => "Organization",
+            Attribute::OrganizationUnit => "Organization unit",
+            Attribute::Country => "Country",
+            Attribute::Locality => "Locality",
+            Attribute::State => "State",
+            Attribute::Surname => "Surname",
+            Attribute::GivenName => "Given name",
+            Attribute::Initials => "Initials",
+            Attribute::GenerationQualifier => "Generation qualifier",
+            Attribute::Pseudonym => "Pseudonym",
+            Attribute::Title => "Title",
+            Attribute::UserId => "User ID",
+            Attribute::SerialNumber => "Serial number",
+            Attribute::DnQualifier => "DN qualifier",
+            Attribute::OrganizationIdentifier => "Organization identifier",
+            Attribute::BusinessCategory => "Business category",
+            Attribute::DomainComponent => "Domain component",
+            Attribute::Street => "Street",
+            Attribute::PostalCode => "Postal code",
+            Attribute::SanDns => "DNS SAN",
+            Attribute::SanIp => "IP SAN",
+            Attribute::SanEmail => "Email SAN",
+            Attribute::SanUri => "URI SAN",
+            Attribute::SpiffeId => "SPIFFE ID",
+            Attribute::SpiffeTrustDomain => "SPIFFE trust domain",
+            Attribute::SpiffePath => "SPIFFE path",
+            Attribute::FingerprintSha256 => "SHA-256 fingerprint",
+            Attribute::FingerprintSha1 => "SHA-1 fingerprint",
+            Attribute::CertificateSerial => "Certificate serial number",
+            Attribute::Issuer => "Issuer",
+            Attribute::IssuerName => "Issuer common name",
+            Attribute::IssuerEmail => "Issuer email address",
+            Attribute::IssuerOrganization => "Issuer organization",
+            Attribute::IssuerOrganizationUnit => "Issuer organization unit",
+            Attribute::IssuerOrganizationIdentifier => "Issuer organization identifier",
+            Attribute::IssuerDomainComponent => "Issuer domain component",
+            Attribute::IssuerSerialNumber => "Issuer serial number",
+            Attribute::IssuerLocality => "Issuer locality",
+            Attribute::IssuerState => "Issuer state",
+            Attribute::IssuerCountry => "Issuer country",
+            Attribute::NotBefore => "Validity start",
+            Attribute::NotAfter => "Validity end",
+            Attribute::DaysUntilExpiry => "Days until expiry",
+            Attribute::ExtendedKeyUsage => "Extended key usage",
+        }
+    }
+
+    /// Header that carries the attribute upstream.
+    pub fn header(self) -> Header {
+        match self {
+            Attribute::Name => Header::Name,
+            Attribute::Email => Header::Email,
+            Attribute::Organization => Header::Organization,
+            Attribute::OrganizationUnit => Header::OrganizationUnit,
+            Attribute::Country => Header::Country,
+            Attribute::Locality => Header::Locality,
+            Attribute::State => Header::State,
+            Attribute::Surname => Header::Surname,
+            Attribute::GivenName => Header::GivenName,
+            Attribute::Initials => Header::Initials,
+            Attribute::GenerationQualifier => Header::GenerationQualifier,
+            Attribute::Pseudonym => Header::Pseudonym,
+            Attribute::Title => Header::Title,
+            Attribute::UserId => Header::UserId,
+            Attribute::SerialNumber => Header::SerialNumber,
+            Attribute::DnQualifier => Header::DnQualifier,
+            Attribute::OrganizationIdentifier => Header::OrganizationIdentifier,
+            Attribute::BusinessCategory => Header::BusinessCategory,
+            Attribute::DomainComponent => Header::DomainComponent,
+            Attribute::Street => Header::Street,
+            Attribute::PostalCode => Header::PostalCode,
+            Attribute::SanDns => Header::SanDns,
+            Attribute::SanIp => Header::SanIp,
+            Attribute::SanEmail => Header::SanEmail,
+            Attribute::SanUri => Header::SanUri,
+            Attribute::SpiffeId => Header::SpiffeId,
+            Attribute::SpiffeTrustDomain => Header::SpiffeTrustDomain,
+            Attribute::SpiffePath => Header::SpiffePath,
+            Attribute::FingerprintSha256 => Header::FingerprintSha256,
+            Attribute::FingerprintSha1 => Header::FingerprintSha1,
+            Attribute::CertificateSerial => Header::CertificateSerial,
+            Attribute::Issuer => Header::Issuer,
+            Attribute::IssuerName => Header::IssuerName,
+            Attribute::IssuerEmail => Header::IssuerEmail,
+            Attribute::IssuerOrganization => Header::IssuerOrganization,
+            Attribute::IssuerOrganizationUnit => Header::IssuerOrganizationUnit,
+            Attribute::IssuerOrganizationIdentifier => Header::IssuerOrganizationIdentifier,
+            Attribute::IssuerDomainComponent => Header::IssuerDomainComponent,
+            Attribute::IssuerSerialNumber => Header::IssuerSerialNumber,
+            Attribute::IssuerLocality => Header::IssuerLocality,
+            Attribute::IssuerState => Header::IssuerState,
+            Attribute::IssuerCountry => Header::IssuerCountry,
+            Attribute::NotBefore => Header::NotBefore,
+            Attribute::NotAfter => Header::NotAfter,
+            Attribute::DaysUntilExpiry => Header::DaysUntilExpiry,
+            Attribute::ExtendedKeyUsage => Header::ExtendedKeyUsage,
+        }
+    }
+}
+
+/// Headers set by the policy on the upstream request.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Header {
+    CertificatePresent,
+    Errors,
+    Name,
+    Email,
+    Organization,
+    OrganizationUnit,
+    Country,
+    Locality,
+    State,
+    Surname,
+    GivenName,
+    Initials,
+    GenerationQualifier,
+    Pseudonym,
+    Title,
+    UserId,
+    SerialNumber,
+    DnQualifier,
+    OrganizationIdentifier,
+    BusinessCategory,
+    DomainComponent,
+    Street,
+    PostalCode,
+    SanDns,
+    PrimaryDns,
+    SanIp,
+    PrimaryIp,
+    SanEmail,
+    SanUri,
+    SpiffeId,
+    SpiffeTrustDomain,
+    SpiffePath,
+    FingerprintSha256,
+    FingerprintSha1,
+    CertificateSerial,
+    Issuer,
+    IssuerName,
+    IssuerEmail,
+    IssuerOrganization,
+    IssuerOrganizationUnit,
+    IssuerOrganizationIdentifier,
+    IssuerDomainComponent,
+    IssuerSerialNumber,
+    IssuerLocality,
+    IssuerState,
+    IssuerCountry,
+    NotBefore,
+    NotAfter,
+    DaysUntilExpiry,
+    ExtendedKeyUsage,
+    Certificate,
+    CertificateChain,
+    ExpiryWarning,
+    PinMatched,
+    Jwt,
+    Identity,
+}
+
+impl Header {
+    pub const ALL: [Header; 56] = [
+        Header::CertificatePresent,
+        Header::Errors,
+        Header::Name,
+        Header::Email,
+        Header::Organization,
+        Header::OrganizationUnit,
+        Header::Country,
+        Header::Locality,
+        Header::State,
+        Header::Surname,
+        Header::GivenName,
+        Header::Initials,
+        Header::GenerationQualifier,
+        Header::Pseudonym,
+        Header::Title,
+        Header::UserId,
+        Header::SerialNumber,
+        Header::DnQualifier,
+        Header::OrganizationIdentifier,
+        Header::BusinessCategory,
+        Header::DomainComponent,
+        Header::Street,
+        Header::PostalCode,
+        Header::SanDns,
+        Header::PrimaryDns,
+        Header::SanIp,
+        Header::PrimaryIp,
+        Header::SanEmail,
+        Header::SanUri,
+        Header::SpiffeId,
+        Header::SpiffeTrustDomain,
+        Header::SpiffePath,
+        Header::FingerprintSha256,
+        Header::FingerprintSha1,
+        Header::CertificateSerial,
+        Header::Issuer,
+        Header::IssuerName,
+        Header::IssuerEmail,
+        Header::IssuerOrganization,
+        Header::IssuerOrganizationUnit,
+        Header::IssuerOrganizationIdentifier,
+        Header::IssuerDomainComponent,
+        Header::IssuerSerialNumber,
+        Header::IssuerLocality,
+        Header::IssuerState,
+        Header::IssuerCountry,
+        Header::NotBefore,
+        Header::NotAfter,
+        Header::DaysUntilExpiry,
+        Header::ExtendedKeyUsage,
+        Header::Certificate,
+        Header::CertificateChain,
+        Header::ExpiryWarning,
+        Header::PinMatched,
+        Header::Jwt,
+        Header::Identity,
+    ];
+
+    /// Parses the header from its output name in the policy configuration.
+    pub fn from_key(key: &str) -> Option<Header> {
+        Header::ALL.iter().copied().find(|header| header.key() == key)
+    }
+
+    /// Name of the output in the policy configuration.
+    pub fn key(self) -> &'static str {
+        match self {
+            Header::CertificatePresent => "certificatePresent",
+            Header::Errors => "errors",
+            Header::Name => "name",
+            Header::Email => "email",
+            Header::Organization => "organization",
+            Header::OrganizationUnit => "organizationUnit",
+            Header::Country => "country",
+            Header::Locality => "locality",
+            Header::State => "state",
+            Header::Surname => "surname",
+            Header::GivenName => "givenName",
+            Header::Initials => "initials",
+            Header::GenerationQualifier => "generationQualifier",
+            Header::Pseudonym => "pseudonym",
+            Header::Title => "title",
+            Header::UserId => "userId",
+            Header::SerialNumber => "serialNumber",
+            Header::DnQualifier => "dnQualifier",
+            Header::OrganizationIdentifier => "organizationIdentifier",
+            Header::BusinessCategory => "businessCategory",
+            Header::DomainComponent => "domainComponent",
+            Header::Street => "street",
+            Header::PostalCode => "postalCode",
+            Header::SanDns => "sanDns",
+            Header::PrimaryDns => "primaryDns",
+            Header::SanIp => "sanIp",
+            Header::PrimaryIp => "primaryIp",
+            Header::SanEmail => "sanEmail",
+            Header::SanUri => "sanUri",
+            Header::SpiffeId => "spiffeId",
+            Header::SpiffeTrustDomain => "spiffeTrustDomain",
+            Header::SpiffePath => "spiffePath",
+            Header::FingerprintSha256 => "fingerprintSha256",
+            Header::FingerprintSha1 => "fingerprintSha1",
+            Header::CertificateSerial => "certificateSerial",
+            Header::Issuer => "issuer",
+            Header::IssuerName => "issuerName",
+            Header::IssuerEmail => "issuerEmail",
+            Header::IssuerOrganization => "issuerOrganization",
+            Header::IssuerOrganizationUnit => "issuerOrganizationUnit",
+            Header::IssuerOrganizationIdentifier => "issuerOrganizationIdentifier",
+            Header::IssuerDomainComponent => "issuerDomainComponent",
+            Header::IssuerSerialNumber => "issuerSerialNumber",
+            Header::IssuerLocality => "issuerLocality",
+            Header::IssuerState => "issuerState",
+            Header::IssuerCountry => "issuerCountry",
+            Header::NotBefore => "notBefore",
+            Header::NotAfter => "notAfter",
+            Header::DaysUntilExpiry => "daysUntilExpiry",
+            Header::ExtendedKeyUsage => "extendedKeyUsage",
+            Header::Certificate => "certificate",
+            Header::CertificateChain => "certificateChain",
+            Header::ExpiryWarning => "expiryWarning",
+            Header::PinMatched => "pinMatched",
+            Header::Jwt => "jwt",
+            Header::Identity => "identity",
+        }
+    }
+
+    /// Header name following the prefix when the configuration does not override it.
+    pub fn suffix(self) -> &'static str {
+        match self {
+            Header::CertificatePresent => "Certificate-Present",
+            Header::Errors => "Certificate-Errors",
+            Header::Name => "Name",
+            Header::Email => "Email",
+            Header::Organization => "Organization",
+            Header::OrganizationUnit => "OrganizationUnit",
+            Header::Country => "Country",
+            Header::Locality => "Locality",
+            Header::State => "State",
+            Header::Surname => "Surname",
+            Header::GivenName => "GivenName",
+            Header::Initials => "Initials",
+            Header::GenerationQualifier => "GenerationQualifier",
+            Header::Pseudonym => "Pseudonym",
+            Header::Title => "Title",
+            Header::UserId => "UserId",
+            Header::SerialNumber => "SerialNumber",
+            Header::DnQualifier => "DnQualifier",
+            Header::OrganizationIdentifier => "OrganizationIdentifier",
+            Header::BusinessCategory => "BusinessCategory",
+            Header::DomainComponent => "DomainComponent",
+            Header::Street => "Street",
+            Header::PostalCode => "PostalCode",
+            Header::SanDns => "SAN-DNS",
+            Header::PrimaryDns => "Primary-DNS",
+            Header::SanIp => "SAN-IP",
+            Header::PrimaryIp => "Primary-IP",
+            Header::SanEmail => "SAN-Email",
+            Header::SanUri => "SAN-URI",
+            Header::SpiffeId => "SPIFFE-ID",
+            Header::SpiffeTrustDomain => "Trust-Domain",
+            Header::SpiffePath => "SPIFFE-Path",
+            Header::FingerprintSha256 => "Fingerprint-SHA256",
+            Header::FingerprintSha1 => "Fingerprint-SHA1",
+            Header::CertificateSerial => "Certificate-Serial",
+            Header::Issuer => "Issuer",
+            Header::IssuerName => "Issuer-Name",
+            Header::IssuerEmail => "Issuer-Email",
+            Header::IssuerOrganization => "Issuer-Organization",
+            Header::IssuerOrganizationUnit => "Issuer-OrganizationUnit",
+            Header::IssuerOrganizationIdentifier => "Issuer-OrganizationIdentifier",
+            Header::IssuerDomainComponent => "Issuer-DomainComponent",
+            Header::IssuerSerialNumber => "Issuer-SerialNumber",
+            Header::IssuerLocality => "Issuer-Locality",
+            Header::IssuerState => "Issuer-State",
+            Header::IssuerCountry => "Issuer-Country",
+            Header::NotBefore => "Cert-Not-Before",
+            Header::NotAfter => "Cert-Not-After",
+            Header::DaysUntilExpiry => "Cert-Days-Until-Expiry",
+            Header::ExtendedKeyUsage => "Extended-Key-Usage",
+            Header::Certificate => "Certificate",
+            Header::CertificateChain => "Certificate-Chain",
+            Header::ExpiryWarning => "Cert-Expiry-Warning",
+            Header::PinMatched => "Pin-Matched",
+            Header::Jwt => "JWT",
+            Header::Identity => "Identity",
+        }
+    }
+}
+
+/// Whether the policy lets requests without a valid client certificate through.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+    /// Reports the certificate details in headers and lets every request through.
+    Observe,
+    /// Rejects requests without a verified client certificate.
+    Enforce,
+}
+
+/// What the policy does with a certificate that misses required attributes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FailureMode {
+    /// Reports the missing attributes in a header and lets the request through.
+    Annotate,
+    /// Denies the request.
+    Reject,
+}
+
+/// Pattern every value of an attribute must match when the attribute is present.
+#[derive(Clone, Debug)]
+pub struct AttributeConstraint {
+    pub attribute: Attribute,
+    pub pattern: Regex,
+}
+
+/// How attributes with several values are written to headers.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MultiValueMode {
+    /// All values in a single header, joined with the separator.
+    Joined(String),
+    /// The first value in the header and, when there are several, every value in a header suffixed with its 1-based index.
+    Indexed,
+    /// All values in a single header as a JSON array of strings.
+    Json,
+}
+
+/// How the peer certificate is forwarded upstream.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CertificateForwarding {
+    pub encoding: CertificateEncoding,
+    /// Whether the chain sent by the client is forwarded too.
+    pub include_chain: bool,
+}
+
+/// Where the CRLs checked for revoked certificates come from.
+#[derive(Clone, Debug)]
+pub struct CrlSettings {
+    /// CRLs given in the configuration.
+    pub crls: Vec<Crl>,
+    /// Certificates of the CRL issuers, whose keys the CRLs are verified with.
+    pub issuers: Vec<Certificate>,
+    /// Service the CRLs are fetched from, along with their path.
+    pub service: Option<(Service, String)>,
+    pub refresh_interval: Duration,
+    /// Whether requests are let through when the revocation status of their certificate is unknown.
+    pub fail_open: bool,
+}
+
+/// How the revocation status of certificates is asked to an OCSP responder.
+#[derive(Clone, Debug)]
+pub struct OcspSettings {
+    pub service: Service,
+    pub path: String,
+    /// Certificates of the issuers of client certificates, besides the chain sent by the client.
+    pub issuers: Vec<Certificate>,
+    /// Seconds a status is cached when the response has no next update.
+    pub cache_ttl: i64,
+    /// Seconds a response without next update is current for, after its this update.
+    pub max_age: i64,
+    /// Whether requests are let through when the revocation status of their certificate is unknown.
+    pub fail_open: bool,
+}
+
+/// Client certificate accepted by the API, by fingerprint and/or public key hash.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Pin {
+    pub name: String,
+    /// SHA-256 fingerprint of the certificate, as lowercase hex.
+    pub fingerprint: Option<String>,
+    /// SHA-256 hash of the SubjectPublicKeyInfo of the certificate.
+    pub public_key_hash: Option<Vec<u8>>,
+}
+
+/// How the JWT asserting the identity of the client is minted.
+#[derive(Debug)]
+pub struct JwtSettings {
+    pub key: SigningKey,
+    pub key_id: Option<String>,
+    pub issuer: Option<String>,
+    pub audience: Option<String>,
+    /// Lifetime of the JWT in seconds, cut short when the certificate expires before.
+    pub ttl: i64,
+    /// Whether the JWT replaces the attribute headers.
+    pub replace_attribute_headers: bool,
+}
+
+/// How bearer tokens bound to the client certificate, as in RFC 8705, are checked.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BoundTokens {
+    /// Lowercase name of the header with the token.
+    pub header: String,
+    /// Whether tokens must be bound to a certificate.
+    pub required: bool,
+    pub rejection: Rejection,
+}
+
+/// How the identity document is written to its header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IdentityEncoding {
+    Json,
+    /// The JSON document encoded as base64url without padding.
+    Base64Url,
+}
+
+/// How the identity of the client is set as a single JSON document.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IdentityHeader {
+    pub encoding: IdentityEncoding,
+    /// Whether the identity header replaces the attribute headers.
+    pub replace_attribute_headers: bool,
+}
+
+/// Part of the certificate an OID is looked up in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OidSource {
+    Extension,
+    Subject,
+    Issuer,
+}
+
+/// Header set with the values found for an OID.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OidHeader {
+    pub oid: String,
+    pub source: OidSource,
+    pub header: String,
+}
+
+/// Policy configuration validated and resolved into the shape used by the filter.
+#[derive(Debug)]
+pub struct Settings {
+    pub mode: Mode,
+    pub missing_certificate: Rejection,
+    pub unverified_certificate: Rejection,
+    pub attributes: Vec<Attribute>,
+    pub required_attributes: Vec<Attribute>,
+    pub attribute_constraints: Vec<AttributeConstraint>,
+    pub failure_mode: FailureMode,
+    pub rejection: Rejection,
+    pub multi_value_mode: MultiValueMode,
+    pub authorization: Authorization,
+    pub authorization_rejection: Rejection,
+    pub forward_certificate: Option<CertificateForwarding>,
+    /// What to do with the XFCC header, `None` leaves it untouched.
+    pub forward_client_cert_details: Option<ForwardMode>,
+    pub client_cert_details: Vec<Detail>,
+    /// Whether certificates outside their validity window are reported as errors.
+    pub check_validity: bool,
+    /// OIDs of the extended key usages a certificate can be used for when key usages are checked.
+    pub allowed_extended_key_usages: Option<Vec<String>>,
+    /// Days before the expiry of a certificate from which requests are flagged.
+    pub expiry_warning_days: Option<i64>,
+    pub oid_headers: Vec<OidHeader>,
+    pub crl: Option<CrlSettings>,
+    pub ocsp: Option<OcspSettings>,
+    pub pins: Vec<Pin>,
+    pub jwt: Option<JwtSettings>,
+    pub bound_tokens: Option<BoundTokens>,
+    pub identity_header: Option<IdentityHeader>,
+    /// Names of the headers of every output, disabled ones included.
+    header_names: HashMap<Header, String>,
+    disabled_outputs: HashSet<Header>,
+    /// Lowercase prefix of the headers owned by the policy.
+    header_namespace: String,
+    /// Lowercase names of the headers set by the policy.
+    managed_headers: HashSet<String>,
+}
+
+impl Settings {
+    /// Builds the settings from the configuration, rejecting unknown values.
+    pub fn from_config(config: &Config) -> Result<Settings> {
+        let mode = match config.mode.as_deref() {
+            None | Some("observe") => Mode::Observe,
+            Some("enforce") => Mode::Enforce,
+            Some(other) => return Err(anyhow!("Unknown mode '{}'", other)),
+        };
+
+        let missing_certificate = match &config.missing_certificate {
+            Some(rejection) => parse_rejection(rejection.status, &rejection.body, &rejection.content_type, 401)?,
+            None => Rejection::new(401),
+        };
+
+        let unverified_certificate = match &config.unverified_certificate {
+            Some(rejection) => parse_rejection(rejection.status, &rejection.body, &rejection.content_type, 403)?,
+            None => Rejection::new(403),
+        };
+
+        let attributes = match &config.attributes {
+            Some(keys) => parse_attributes(keys)?,
+            // The attributes added since the first version are only forwarded on request
+            None => DEFAULT_ATTRIBUTES.to_vec(),
+        };
+
+        let required_attributes = match &config.required_attributes {
+            Some(keys) => parse_attributes(keys)?,
+            None => vec![Attribute::Name, Attribute::Email],
+        };
+
+        let mut attribute_constraints = Vec::new();
+        for constraint in config.attribute_constraints.iter().flatten() {
+            let attribute = Attribute::from_key(&constraint.attribute)
+                .ok_or_else(|| anyhow!("Unknown attribute '{}'", constraint.attribute))?;
+            // Patterns must match the whole value.
+            let pattern = Regex::new(&format!("^(?:{})$", constraint.pattern))
+                .map_err(|err| anyhow!("Invalid pattern for attribute '{}': {}", constraint.attribute, err))?;
+            attribute_constraints.push(AttributeConstraint { attribute, pattern });
+        }
+
+        let failure_mode = match config.failure_mode.as_deref() {
+            None | Some("annotate") => FailureMode::Annotate,
+            Some("reject") => FailureMode::Reject,
+            Some(other) => return Err(anyhow!("Unknown failure mode '{}'", other)),
+        };
+
+        let rejection = match &config.rejection {
+            Some(rejection) => parse_rejection(rejection.status, &rejection.body, &rejection.content_type, 403)?,
+            None => Rejection::new(403),
+        };
+
+        let multi_value_mode = match config.multi_value_mode.as_deref() {
+            None | Some("joined") => {
+                MultiValueMode::Joined(config.multi_value_separator.clone().unwrap_or_else(|| ",".to_string()))
+            }
+            Some("indexed") => MultiValueMode::Indexed,
+            Some("json") => MultiValueMode::Json,
+            Some(other) => return Err(anyhow!("Unknown multi-value mode '{}'", other)),
+        };
+
+        let matching = match config.authorization_matching.as_deref() {
+            None | Some("all") => RuleMatching::All,
+            Some("firstMatch") => RuleMatching::FirstMatch,
+            Some("mostSpecific") => RuleMatching::MostSpecific,
+            Some(other) => return Err(anyhow!("Unknown authorization matching '{}'", other)),
+        };
+        let mut authorization = Authorization {
+            rules: Vec::new(),
+            matching,
+        };
+        for (index, rule) in config.authorization_rules.iter().flatten().enumerate() {
+            let name = rule.name.clone().unwrap_or_else(|| format!("rule {}", index + 1));
+            let effect = match rule.effect.as_deref() {
+                None | Some("allow") => Effect::Allow,
+                Some("deny") => Effect::Deny,
+                Some(other) => return Err(anyhow!("Unknown effect '{}' in authorization rule '{}'", other, name)),
+            };
+
+            let mut conditions = Vec::new();
+            for condition in &rule.conditions {
+                let attribute = Attribute::from_key(&condition.attribute).ok_or_else(|| {
+                    anyhow!(
+                        "Unknown attribute '{}' in authorization rule '{}'",
+                        condition.attribute,
+                        name
+                    )
+                })?;
+                let matcher = parse_matcher(condition.matches.as_deref(), &condition.value)
+                    .map_err(|err| anyhow!("Invalid condition in authorization rule '{}': {}", name, err))?;
+                conditions.push(Condition { attribute, matcher });
+            }
+
+            authorization.rules.push(Rule {
+                name,
+                effect,
+                paths: rule.paths.clone().unwrap_or_default(),
+                methods: rule.methods.clone().unwrap_or_default(),
+                conditions,
+            });
+        }
+
+        let authorization_rejection = match &config.authorization_rejection {
+            Some(rejection) => parse_rejection(rejection.status, &rejection.body, &rejection.content_type, 403)?,
+            None => Rejection::new(403),
+        };
+
+        if config.forward_certificate.is_some() {
+            require_peer_certificate(config, "forwardCertificate")?;
+        }
+        let forward_certificate = match &config.forward_certificate {
+            Some(forward) => Some(CertificateForwarding {
+                encoding: match forward.encoding.as_deref() {
+                    None | Some("urlEncodedPem") => CertificateEncoding::UrlEncodedPem,
+                    Some("base64Der") => CertificateEncoding::Base64Der,
+                    Some(other) => return Err(anyhow!("Unknown certificate encoding '{}'", other)),
+                },
+                include_chain: forward.include_chain.unwrap_or(false),
+            }),
+            None => None,
+        };
+
+        let forward_client_cert_details = match config.forward_client_cert_details.as_deref() {
+            None => None,
+            Some("sanitize") => Some(ForwardMode::Sanitize),
+            Some("forwardOnly") => Some(ForwardMode::ForwardOnly),
+            Some("appendForward") => Some(ForwardMode::AppendForward),
+            Some("sanitizeSet") => Some(ForwardMode::SanitizeSet),
+            Some("alwaysForwardOnly") => Some(ForwardMode::AlwaysForwardOnly),
+            Some(other) => return Err(anyhow!("Unknown forward client cert details '{}'", other)),
+        };
+
+        let mut client_cert_details = Vec::new();
+        for key in config.set_current_client_cert_details.iter().flatten() {
+            client_cert_details.push(match key.as_str() {
+                "subject" => Detail::Subject,
+                "cert" => Detail::Cert,
+                "chain" => Detail::Chain,
+                "uri" => Detail::Uri,
+                "dns" => Detail::Dns,
+                other => return Err(anyhow!("Unknown client cert detail '{}'", other)),
+            });
+        }
+
+        let allowed_extended_key_usages = if config.check_key_usage.unwrap_or(false) {
+            require_peer_certificate(config, "checkKeyUsage")?;
+            let keys = match &config.allowed_extended_key_usages {
+                Some(keys) => keys.clone(),
+                None => vec!["clientAuth".to_string()],
+            };
+            Some(
+                keys.iter()
+                    .map(|key| parse_extended_key_usage(key))
+                    .collect::<Result<Vec<_>>>()?,
+            )
+        } else {
+            None
+        };
+
+        let expiry_warning_days = match config.expiry_warning_days {
+            Some(days) if days < 0 => return Err(anyhow!("Invalid expiry warning days {}", days)),
+            days => days,
+        };
+
+        // Values of all sources are read from the decoded certificate
+        if config.oid_headers.as_ref().is_some_and(|oid_headers| !oid_headers.is_empty()) {
+            require_peer_certificate(config, "oidHeaders")?;
+        }
+        let mut oid_headers = Vec::new();
+        for oid_header in config.oid_headers.iter().flatten() {
+            if !is_object_identifier(&oid_header.oid) {
+                return Err(anyhow!("Invalid OID '{}' for header '{}'", oid_header.oid, oid_header.header));
+            }
+            let source = match oid_header.source.as_deref() {
+                None | Some("extension") => OidSource::Extension,
+                Some("subject") => OidSource::Subject,
+                Some("issuer") => OidSource::Issuer,
+                Some(other) => return Err(anyhow!("Unknown OID source '{}'", other)),
+            };
+            oid_headers.push(OidHeader {
+                oid: oid_header.oid.clone(),
+                source,
+                header: oid_header.header.clone(),
+            });
+        }
+
+        let crl = match &config.crl {
+            Some(crl) => {
+                let issuers = parse_issuers(&crl.issuers, "CRL")?;
+                if issuers.is_empty() {
+                    return Err(anyhow!("CRL signatures can not be verified without issuer certificates"));
+                }
+                let mut crls = Vec::new();
+                for pem in crl.pems.iter().flatten() {
+                    crls.extend(revocation::parse_crls(pem.as_bytes(), &issuers).map_err(|err| anyhow!(err))?);
+                }
+                let refresh_interval = match crl.refresh_interval {
+                    None => 3600,
+                    Some(seconds) if seconds > 0 => seconds as u64,
+                    Some(seconds) => return Err(anyhow!("Invalid CRL refresh interval {}", seconds)),
+                };
+                Some(CrlSettings {
+                    crls,
+                    issuers,
+                    service: crl
+                        .service
+                        .clone()
+                        .map(|service| (service, crl.path.clone().unwrap_or_else(|| "/".to_string()))),
+                    refresh_interval: Duration::from_secs(refresh_interval),
+                    fail_open: crl.fail_open.unwrap_or(false),
+                })
+            }
+            None => None,
+        };
+
+        let ocsp = match &config.ocsp {
+            Some(ocsp) => {
+                let issuers = parse_issuers(ocsp.issuers.as_deref().unwrap_or_default(), "OCSP")?;
+                let cache_ttl = match ocsp.cache_ttl {
+                    None => 300,
+                    Some(seconds) if seconds >= 0 => seconds,
+                    Some(seconds) => return Err(anyhow!("Invalid OCSP cache TTL {}", seconds)),
+                };
+                let max_age = match ocsp.max_age {
+                    None => 3600,
+                    Some(seconds) if seconds > 0 => seconds,
+                    Some(seconds) => return Err(anyhow!("Invalid OCSP max age {}", seconds)),
+                };
+                Some(OcspSettings {
+                    service: ocsp.service.clone(),
+                    path: ocsp.path.clone().unwrap_or_else(|| "/".to_string()),
+                    issuers,
+                    cache_ttl,
+                    max_age,
+                    fail_open: ocsp.fail_open.unwrap_or(false),
+                })
+            }
+            None => None,
+        };
+
+        if crl.is_some() {
+            require_peer_certificate(config, "crl")?;
+        }
+        if ocsp.is_some() {
+            require_peer_certificate(config, "ocsp")?;
+        }
+
+        let mut pins = Vec::new();
+        for (index, pin) in config.pins.iter().flatten().enumerate() {
+            let name = pin.name.clone().unwrap_or_else(|| format!("pin {}", index + 1));
+            let fingerprint = match &pin.fingerprint {
+                Some(fingerprint) => {
+                    let fingerprint = fingerprint.replace(':', "").to_ascii_lowercase();
+                    if fingerprint.len() != 64 || !fingerprint.bytes().all(|b| b.is_ascii_hexdigit()) {
+                        return Err(anyhow!("Invalid SHA-256 fingerprint in pin '{}'", name));
+                    }
+                    Some(fingerprint)
+                }
+                None => None,
+            };
+            let public_key_hash = match &pin.public_key_hash {
+                Some(hash) => {
+                    // The public key is only known from the decoded certificate
+                    require_peer_certificate(config, "publicKeyHash")?;
+                    match STANDARD.decode(hash) {
+                        Ok(hash) if hash.len() == 32 => Some(hash),
+                        _ => return Err(anyhow!("Invalid public key hash in pin '{}'", name)),
+                    }
+                }
+                None => None,
+            };
+            if fingerprint.is_none() && public_key_hash.is_none() {
+                return Err(anyhow!("Pin '{}' has neither fingerprint nor public key hash", name));
+            }
+            pins.push(Pin {
+                name,
+                fingerprint,
+                public_key_hash,
+            });
+        }
+
+        let jwt = match &config.jwt {
+            Some(jwt) => Some(JwtSettings {
+                key: SigningKey::new(&jwt.algorithm, &jwt.key).map_err(|err| anyhow!("Invalid JWT key: {}", err))?,
+                key_id: jwt.key_id.clone(),
+                issuer: jwt.issuer.clone(),
+                audience: jwt.audience.clone(),
+                ttl: match jwt.ttl {
+                    None => 300,
+                    Some(seconds) if seconds > 0 => seconds,
+                    Some(seconds) => return Err(anyhow!("Invalid JWT TTL {}", seconds)),
+                },
+                replace_attribute_headers: jwt.replace_attribute_headers.unwrap_or(false),
+            }),
+            None => None,
+        };
+
+        let bound_tokens = if config.check_bound_tokens.unwrap_or(false) {
+            Some(BoundTokens {
+                header: config
+                    .bound_token_header
+                    .as_deref()
+                    .unwrap_or("authorization")
+                    .to_ascii_lowercase(),
+                required: config.require_bound_tokens.unwrap_or(false),
+                rejection: match &config.bound_token_rejection {
+                    Some(rejection) => {
+                        parse_rejection(rejection.status, &rejection.body, &rejection.content_type, 401)?
+                    }
+                    None => Rejection::new(401),
+                },
+            })
+        } else {
+            None
+        };
+
+        let identity_header = match &config.identity_header {
+            Some(identity_header) => Some(IdentityHeader {
+                encoding: match identity_header.encoding.as_deref() {
+                    None | Some("json") => IdentityEncoding::Json,
+                    Some("base64url") => IdentityEncoding::Base64Url,
+                    Some(other) => return Err(anyhow!("Unknown identity header encoding '{}'", other)),
+                },
+                replace_attribute_headers: identity_header.replace_attribute_headers.unwrap_or(false),
+            }),
+            None => None,
+        };
+
+        let header_prefix = config.header_prefix.as_deref().unwrap_or(DEFAULT_HEADER_PREFIX);
+        let mut header_names: HashMap<Header, String> = Header::ALL
+            .iter()
+            .map(|header| (*header, format!("{}{}", header_prefix, header.suffix())))
+            .collect();
+        for header_name in config.header_names.iter().flatten() {
+            let header = Header::from_key(&header_name.output)
+                .ok_or_else(|| anyhow!("Unknown header output '{}'", header_name.output))?;
+            header_names.insert(header, header_name.header.clone());
+        }
+
+        let mut disabled_outputs = HashSet::new();
+        for key in config.disabled_outputs.iter().flatten() {
+            disabled_outputs.insert(Header::from_key(key).ok_or_else(|| anyhow!("Unknown header output '{}'", key))?);
+        }
+
+        let header_namespace = config
+            .header_namespace
+            .as_deref()
+            .unwrap_or(header_prefix)
+            .to_ascii_lowercase();
+
+        let mut settings = Settings {
+            mode,
+            missing_certificate,
+            unverified_certificate,
+            attributes,
+            required_attributes,
+            attribute_constraints,
+            failure_mode,
+            rejection,
+            multi_value_mode,
+            authorization,
+            authorization_rejection,
+            forward_certificate,
+            forward_client_cert_details,
+            client_cert_details,
+            check_validity: config.check_validity.unwrap_or(true),
+            allowed_extended_key_usages,
+            expiry_warning_days,
+            oid_headers,
+            crl,
+            ocsp,
+            pins,
+            jwt,
+            bound_tokens,
+            identity_header,
+            header_names,
+            disabled_outputs,
+            header_namespace,
+            managed_headers: HashSet::new(),
+        };
+        // Disabled outputs are still removed from the requests of clients
+        settings.managed_headers = settings
+            .header_names
+            .values()
+            .map(|name| name.to_ascii_lowercase())
+            .chain(settings.oid_headers.iter().map(|oid_header| oid_header.header.to_ascii_lowercase()))
+            .collect();
+        Ok(settings)
+    }
+
+    /// Returns the name of the header used for the given output, `None` when the output is disabled.
+    pub fn header_name(&self, header: Header) -> Option<&str> {
+        if self.disabled_outputs.contains(&header) {
+            return None;
+        }
+        self.header_names.get(&header).map(String::as_str)
+    }
+
+    /// Tells whether a request header belongs to the policy, either because it is in the managed
+    /// namespace or because the policy sets it, including the indexed variants of multi-value headers.
+    pub fn is_managed_header(&self, name: &str) -> bool {
+        let name = name.to_ascii_lowercase();
+        if !self.header_namespace.is_empty() && name.starts_with(&self.header_namespace) {
+            return true;
+        }
+        if self.managed_headers.contains(&name) {
+            return true;
+        }
+        match name.rsplit_once('-') {
+            Some((base, index)) => {
+                !index.is_empty() && index.bytes().all(|b| b.is_ascii_digit()) && self.managed_headers.contains(base)
+            }
+            None => false,
+        }
+    }
+}
+
+fn parse_attributes(keys: &[String]) -> Result<Vec<Attribute>> {
+    keys.iter()
+        .map(|key| Attribute::from_key(key).ok_or_else(|| anyhow!("Unknown attribute '{}'", key)))
+        .collect()
+}
+
+/// Refuses a feature reading the peer certificate PEM unless the gateway provides it, as Envoy only exposes the
+/// subject, SANs and digest of the peer certificate.
+fn require_peer_certificate(config: &Config, feature: &str) -> Result<()> {
+    if config.peer_certificate_available.unwrap_or(false) {
+        return Ok(());
+    }
+    Err(anyhow!(
+        "{} needs the peer certificate PEM in the connection.peer_certificate property, set \
+         peerCertificateAvailable once the gateway provides it",
+        feature
+    ))
+}
+
+/// Decodes the issuer certificates of the PEM documents, named after the revocation check using them in errors.
+fn parse_issuers(pems: &[String], check: &str) -> Result<Vec<Certificate>> {
+    let mut issuers = Vec::new();
+    for pem in pems {
+        for certificate_der in
+            encoding::certificates_der(pem).map_err(|err| anyhow!("Malformed {} issuer PEM: {}", check, err))?
+        {
+            issuers.push(
+                Certificate::parse(&certificate_der)
+                    .map_err(|err| anyhow!("Malformed {} issuer certificate: {}", check, err))?,
+            );
+        }
+    }
+    Ok(issuers)
+}
+
+fn parse_matcher(matches: Option<&str>, value: &str) -> Result<Matcher> {
+    match matches {
+        None | Some("exact") => Ok(Matcher::Exact(value.to_string())),
+        Some("prefix") => Ok(Matcher::Prefix(value.to_string())),
+        Some("glob") => Ok(Matcher::Glob(value.to_string())),
+        // Patterns must match the whole value, like the attribute constraints
+        Some("regex") => Ok(Matcher::Regex(Regex::new(&format!("^(?:{})$", value))?)),
+        Some(other) => Err(anyhow!("unknown match type '{}'", other)),
+    }
+}
+
+/// Resolves an extended key usage given by name, such as `clientAuth`, or as a dotted OID.
+fn parse_extended_key_usage(key: &str) -> Result<String> {
+    if let Some((_, oid)) = EXTENDED_KEY_USAGES.iter().find(|(name, _)| *name == key) {
+        return Ok(oid.to_string());
+    }
+    if !is_object_identifier(key) {
+        return Err(anyhow!("Unknown extended key usage '{}'", key));
+    }
+    Ok(key.to_string())
+}
+
+/// Tells whether the value is a dotted OID, such as `1.3.6.1.5.5.7.3.2`.
+fn is_object_identifier(value: &str) -> bool {
+    let arcs: Vec<&str> = value.split('.').collect();
+    arcs.len() >= 2 && arcs.iter().all(|arc| !arc.is_empty() && arc.bytes().all(|b| b.is_ascii_digit()))
+}
+
+/// Builds a configured rejection, falling back to the default status and to the `text/plain` content type.
+fn parse_rejection(
+    status: Option<i64>,
+    body: &Option<String>,
+    content_type: &Option<String>,
+    default_status: u32,
+) -> Result<Rejection> {
+    Ok(Rejection {
+        status: parse_status(status, default_status)?,
+        body: body.clone(),
+        content_type: content_type.clone().unwrap_or_else(|| BODY_CONTENT_TYPE.to_string()),
+    })
+}
+
+fn parse_status(status: Option<i64>, default: u32) -> Result<u32> {
+    match status {
+        None => Ok(default),
+        Some(status) if (400..=599).contains(&status) => Ok(status as u32),
+        Some(status) => Err(anyhow!(
+            "Invalid rejection status {}, expected a 4xx or 5xx code",
+            status
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const CA_PEM: &str = include_str!("../tests/resources/ca.pem");
+
+    fn settings(config: &str) -> Settings {
+        Settings::from_config(&serde_json::from_str(config).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn forwards_baseline_attributes_by_default() {
+        let settings = settings("{}");
+
+        assert_eq!(settings.attributes, DEFAULT_ATTRIBUTES);
+        assert!(!settings.attributes.contains(&Attribute::Issuer));
+    }
+
+    #[test]
+    fn anchors_regex_conditions() {
+        let matcher = parse_matcher(Some("regex"), "payments|billing").unwrap();
+
+        assert!(matcher.matches("payments"));
+        assert!(matcher.matches("billing"));
+        assert!(!matcher.matches("evil-payments-x"));
+        assert!(!matcher.matches("billing-x"));
+    }
+
+    #[test]
+    fn parses_rejection_content_type() {
+        let settings = settings(
+            r#"{
+                "missingCertificate": {"body": "<h1>Certificate required</h1>", "contentType": "text/html"},
+                "rejection": {"body": "Forbidden"}
+            }"#,
+        );
+
+        assert_eq!(settings.missing_certificate.content_type, "text/html");
+        assert_eq!(settings.rejection.content_type, "text/plain");
+    }
+
+    #[test]
+    fn manages_headers_in_namespace_and_overrides() {
+        let settings = settings(r#"{"headerNames": [{"output": "name", "header": "X-Client-CN"}]}"#);
+
+        assert!(settings.is_managed_header("x-peer-email"));
+        assert!(settings.is_managed_header("X-Peer-Anything"));
+        assert!(settings.is_managed_header("x-client-cn"));
+        assert!(settings.is_managed_header("X-Client-CN-2"));
+        assert!(!settings.is_managed_header("X-Client-CN-Extra"));
+        assert!(!settings.is_managed_header("authorization"));
+    }
+
+    #[test]
+    fn manages_only_set_headers_with_empty_namespace() {
+        let settings = settings(r#"{"headerNamespace": ""}"#);
+
+        assert!(settings.is_managed_header("X-Peer-Name"));
+        assert!(settings.is_managed_header("X-Peer-OrganizationUnit-1"));
+        assert!(!settings.is_managed_header("X-Peer-Custom"));
+    }
+
+    #[test]
+    fn derives_header_names_from_prefix() {
+        let settings = settings(
+            r#"{
+                "headerPrefix": "X-Client-Cert-",
+                "headerNames": [{"output": "name", "header": "X-Client-CN"}],
+                "disabledOutputs": ["email"]
+            }"#,
+        );
+
+        assert_eq!(settings.header_name(Header::SanDns), Some("X-Client-Cert-SAN-DNS"));
+        assert_eq!(settings.header_name(Header::Name), Some("X-Client-CN"));
+        assert_eq!(settings.header_name(Header::Email), None);
+        // The namespace follows the prefix, disabled outputs are still removed
+        assert!(settings.is_managed_header("X-Client-Cert-Anything"));
+        assert!(settings.is_managed_header("x-client-cert-email"));
+        assert!(!settings.is_managed_header("X-Peer-Name"));
+
+        let config = serde_json::from_str(r#"{"disabledOutputs": ["everything"]}"#).unwrap();
+        assert!(Settings::from_config(&config).is_err());
+    }
+
+    #[test]
+    fn manages_oid_headers() {
+        let config = json!({
+            "oidHeaders": [{"oid": "1.3.6.1.4.1.99999.1", "header": "X-Tenant"}],
+            "peerCertificateAvailable": true
+        });
+        let settings = Settings::from_config(&serde_json::from_value(config).unwrap()).unwrap();
+
+        assert_eq!(
+            settings.oid_headers,
+            vec![OidHeader {
+                oid: "1.3.6.1.4.1.99999.1".to_string(),
+                source: OidSource::Extension,
+                header: "X-Tenant".to_string(),
+            }]
+        );
+        assert!(settings.is_managed_header("x-tenant"));
+
+        let config = serde_json::from_value(json!({
+            "oidHeaders": [{"oid": "tenant", "header": "X-Tenant"}],
+            "peerCertificateAvailable": true
+        }))
+        .unwrap();
+        assert!(Settings::from_config(&config).is_err());
+    }
+
+    #[test]
+    fn parses_pins() {
+        let settings = settings(
+            r#"{"pins": [
+                {"name": "partner", "fingerprint": "8F:E1:00:A8:88:8A:14:E1:62:A5:53:24:A3:1C:9C:1A:07:0D:93:38:14:B7:2C:A9:A9:20:11:15:0D:13:C0:63"},
+                {"publicKeyHash": "Gh26dVb+kVhFUP+yXKCEwKgwOwrnJLDb3Mr+tHMeeSM="}
+            ], "peerCertificateAvailable": true}"#,
+        );
+
+        assert_eq!(settings.pins[0].name, "partner");
+        assert_eq!(
+            settings.pins[0].fingerprint.as_deref(),
+            Some("8fe100a8888a14e162a55324a31c9c1a070d933814b72ca9a92011150d13c063")
+        );
+        assert_eq!(settings.pins[1].name, "pin 2");
+        assert_eq!(settings.pins[1].public_key_hash.as_ref().map(Vec::len), Some(32));
+
+        for pins in [r#"[{"fingerprint": "8fe1"}]"#, r#"[{"publicKeyHash": "AAAA"}]"#, r#"[{"name": "empty"}]"#] {
+            let config = format!(r#"{{"pins": {}, "peerCertificateAvailable": true}}"#, pins);
+            let config = serde_json::from_str(&config).unwrap();
+            assert!(Settings::from_config(&config).is_err());
+        }
+    }
+
+    #[test]
+    fn parses_identity_header() {
+        let settings = settings(r#"{"identityHeader": {"encoding": "base64url"}}"#);
+
+        assert_eq!(
+            settings.identity_header,
+            Some(IdentityHeader {
+                encoding: IdentityEncoding::Base64Url,
+                replace_attribute_headers: false,
+            })
+        );
+        assert!(settings.is_managed_header("x-peer-identity"));
+
+        let config = serde_json::from_str(r#"{"identityHeader": {"encoding": "yaml"}}"#).unwrap();
+        assert!(Settings::from_config(&config).is_err());
+    }
+
+    #[test]
+    fn requires_the_peer_certificate_for_revocation() {
+        let crl = json!({"issuers": [CA_PEM]});
+        let config = serde_json::from_value(json!({"crl": crl})).unwrap();
+        let err = Settings::from_config(&config).unwrap_err();
+        assert!(err.to_string().contains("peerCertificateAvailable"));
+
+        let config = serde_json::from_value(json!({"crl": crl, "peerCertificateAvailable": true})).unwrap();
+        assert!(Settings::from_config(&config).unwrap().crl.is_some());
+    }
+
+    #[test]
+    fn requires_the_peer_certificate_for_certificate_values() {
+        for config in [
+            json!({"forwardCertificate": {}}),
+            json!({"oidHeaders": [{"oid": "1.3.6.1.4.1.99999.1", "header": "X-Tenant", "source": "subject"}]}),
+        ] {
+            let err = Settings::from_config(&serde_json::from_value(config).unwrap()).unwrap_err();
+            assert!(err.to_string().contains("peerCertificateAvailable"));
+        }
+
+        let settings = settings(r#"{"forwardCertificate": {}, "peerCertificateAvailable": true}"#);
+        assert!(settings.forward_certificate.is_some());
+    }
+
+    #[test]
+    fn requires_the_peer_certificate_for_key_usage() {
+        let config = serde_json::from_value(json!({"checkKeyUsage": true})).unwrap();
+        let err = Settings::from_config(&config).unwrap_err();
+        assert!(err.to_string().contains("peerCertificateAvailable"));
+
+        let enabled = settings(r#"{"checkKeyUsage": true, "peerCertificateAvailable": true}"#);
+        assert_eq!(enabled.allowed_extended_key_usages, Some(vec!["1.3.6.1.5.5.7.3.2".to_string()]));
+        assert_eq!(settings(r#"{"checkKeyUsage": false}"#).allowed_extended_key_usages, None);
+    }
+
+    #[test]
+    fn requires_the_peer_certificate_for_public_key_pins() {
+        let pins = json!([{"publicKeyHash": "Gh26dVb+kVhFUP+yXKCEwKgwOwrnJLDb3Mr+tHMeeSM="}]);
+        let config = serde_json::from_value(json!({"pins": pins})).unwrap();
+        let err = Settings::from_config(&config).unwrap_err();
+        assert!(err.to_string().contains("peerCertificateAvailable"));
+
+        let config = serde_json::from_value(json!({"pins": pins, "peerCertificateAvailable": true})).unwrap();
+        assert_eq!(Settings::from_config(&config).unwrap().pins.len(), 1);
+
+        // Fingerprints are provided by the gateway
+        let pins = json!([{"fingerprint": "8fe100a8888a14e162a55324a31c9c1a070d933814b72ca9a92011150d13c063"}]);
+        let config = serde_json::from_value(json!({"pins": pins})).unwrap();
+        assert_eq!(Settings::from_config(&config).unwrap().pins.len(), 1);
+    }
+}
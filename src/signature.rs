@@ -0,0 +1,290 @@
+// Copyright 2023 Salesforce, Inc. All rights reserved.
+use crate::der::{self, DerError, Reader, BIT_STRING, OBJECT_IDENTIFIER, SEQUENCE};
+use rsa::pkcs1::DecodeRsaPublicKey;
+use rsa::signature::hazmat::PrehashVerifier;
+use rsa::signature::Verifier;
+use sha2::digest::const_oid::AssociatedOid;
+use sha2::{Digest, Sha256, Sha384, Sha512};
+use std::convert::TryFrom;
+use std::fmt;
+
+const ECDSA_WITH_SHA256: &str = "1.2.840.10045.4.3.2";
+const ECDSA_WITH_SHA384: &str = "1.2.840.10045.4.3.3";
+const ECDSA_WITH_SHA512: &str = "1.2.840.10045.4.3.4";
+const SHA256_WITH_RSA_ENCRYPTION: &str = "1.2.840.113549.1.1.11";
+const SHA384_WITH_RSA_ENCRYPTION: &str = "1.2.840.113549.1.1.12";
+const SHA512_WITH_RSA_ENCRYPTION: &str = "1.2.840.113549.1.1.13";
+const EC_PUBLIC_KEY: &str = "1.2.840.10045.2.1";
+const P256: &str = "1.2.840.10045.3.1.7";
+const P384: &str = "1.3.132.0.34";
+const RSA_ENCRYPTION: &str = "1.2.840.113549.1.1.1";
+
+/// Reasons a signature is not accepted.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SignatureError {
+    Malformed(DerError),
+    /// The pair of signature algorithm and key type, ECDSA P-256 or P-384 and RSA with SHA-256, SHA-384 or SHA-512 only
+    /// are supported.
+    UnsupportedAlgorithm(String),
+    InvalidKey,
+    Mismatch,
+}
+
+impl fmt::Display for SignatureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SignatureError::Malformed(err) => write!(f, "malformed signature: {}", err),
+            SignatureError::UnsupportedAlgorithm(algorithm) => {
+                write!(f, "unsupported signature algorithm {}", algorithm)
+            }
+            SignatureError::InvalidKey => f.write_str("invalid public key"),
+            SignatureError::Mismatch => f.write_str("signature does not match"),
+        }
+    }
+}
+
+impl From<DerError> for SignatureError {
+    fn from(err: DerError) -> SignatureError {
+        SignatureError::Malformed(err)
+    }
+}
+
+/// Signed part of a certificate, CRL or OCSP response, with its signature.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Signed<'a> {
+    /// Contents of the signed SEQUENCE.
+    pub tbs: &'a [u8],
+    /// Dotted OID of the signature algorithm.
+    pub algorithm: String,
+    /// Contents of the signature BIT STRING, without the unused bits byte.
+    pub signature: &'a [u8],
+}
+
+impl<'a> Signed<'a> {
+    /// Decodes a DER encoded signed structure.
+    pub fn parse(signed_der: &'a [u8]) -> Result<Signed<'a>, DerError> {
+        Signed::read(&mut Reader::new(Reader::new(signed_der).read_tag(SEQUENCE)?))
+    }
+
+    /// Reads the signed part, algorithm and signature from the fields of a signed structure, leaving the fields
+    /// after them, like the certificates of OCSP responses.
+    pub fn read(fields: &mut Reader<'a>) -> Result<Signed<'a>, DerError> {
+        let tbs = fields.read_tag(SEQUENCE)?;
+        let mut algorithm = Reader::new(fields.read_tag(SEQUENCE)?);
+        let algorithm = der::object_identifier(algorithm.read_tag(OBJECT_IDENTIFIER)?)?;
+        let signature = match fields.read_tag(BIT_STRING)? {
+            [0, signature @ ..] => signature,
+            _ => return Err(DerError::InvalidLength),
+        };
+        Ok(Signed {
+            tbs,
+            algorithm,
+            signature,
+        })
+    }
+
+    /// Checks the signature with the given DER encoded SubjectPublicKeyInfo.
+    pub fn verify(&self, subject_public_key_info: &[u8]) -> Result<(), SignatureError> {
+        let mut public_key_info = Reader::new(Reader::new(subject_public_key_info).read_tag(SEQUENCE)?);
+        let mut key_algorithm = Reader::new(public_key_info.read_tag(SEQUENCE)?);
+        let key_type = der::object_identifier(key_algorithm.read_tag(OBJECT_IDENTIFIER)?)?;
+        let public_key = match public_key_info.read_tag(BIT_STRING)? {
+            [0, key @ ..] => key,
+            _ => return Err(DerError::InvalidLength.into()),
+        };
+        // The signature covers the whole encoding of the signed part, DER being canonical
+        let message = der::encode(SEQUENCE, self.tbs);
+
+        match (self.algorithm.as_str(), key_type.as_str()) {
+            (ECDSA_WITH_SHA256 | ECDSA_WITH_SHA384 | ECDSA_WITH_SHA512, EC_PUBLIC_KEY) => {
+                let digest = match self.algorithm.as_str() {
+                    ECDSA_WITH_SHA256 => Sha256::digest(&message).to_vec(),
+                    ECDSA_WITH_SHA384 => Sha384::digest(&message).to_vec(),
+                    _ => Sha512::digest(&message).to_vec(),
+                };
+                let curve = der::object_identifier(key_algorithm.read_tag(OBJECT_IDENTIFIER)?)?;
+                match curve.as_str() {
+                    P256 => {
+                        let key = p256::ecdsa::VerifyingKey::from_sec1_bytes(public_key)
+                            .map_err(|_| SignatureError::InvalidKey)?;
+                        let signature =
+                            p256::ecdsa::Signature::from_der(self.signature).map_err(|_| SignatureError::Mismatch)?;
+                        key.verify_prehash(&digest, &signature)
+                            .map_err(|_| SignatureError::Mismatch)
+                    }
+                    P384 => {
+                        let key = p384::ecdsa::VerifyingKey::from_sec1_bytes(public_key)
+                            .map_err(|_| SignatureError::InvalidKey)?;
+                        let signature =
+                            p384::ecdsa::Signature::from_der(self.signature).map_err(|_| SignatureError::Mismatch)?;
+                        key.verify_prehash(&digest, &signature)
+                            .map_err(|_| SignatureError::Mismatch)
+                    }
+                    _ => Err(SignatureError::UnsupportedAlgorithm(format!(
+                        "{} on curve {}",
+                        self.algorithm, curve
+                    ))),
+                }
+            }
+            (SHA256_WITH_RSA_ENCRYPTION, RSA_ENCRYPTION) => verify_rsa::<Sha256>(public_key, &message, self.signature),
+            (SHA384_WITH_RSA_ENCRYPTION, RSA_ENCRYPTION) => verify_rsa::<Sha384>(public_key, &message, self.signature),
+            (SHA512_WITH_RSA_ENCRYPTION, RSA_ENCRYPTION) => verify_rsa::<Sha512>(public_key, &message, self.signature),
+            _ => Err(SignatureError::UnsupportedAlgorithm(self.algorithm.clone())),
+        }
+    }
+}
+
+/// Checks a PKCS#1 v1.5 signature with the given PKCS#1 encoded RSA public key, hashing with `D`.
+fn verify_rsa<D: Digest + AssociatedOid>(
+    public_key: &[u8],
+    message: &[u8],
+    signature: &[u8],
+) -> Result<(), SignatureError> {
+    let key = rsa::RsaPublicKey::from_pkcs1_der(public_key).map_err(|_| SignatureError::InvalidKey)?;
+    let signature = rsa::pkcs1v15::Signature::try_from(signature).map_err(|_| SignatureError::Mismatch)?;
+    rsa::pkcs1v15::VerifyingKey::<D>::new(key)
+        .verify(message, &signature)
+        .map_err(|_| SignatureError::Mismatch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoding;
+    use crate::x509::Certificate;
+    use rsa::pkcs8::{DecodePrivateKey, EncodePublicKey};
+    use rsa::signature::hazmat::PrehashSigner;
+    use rsa::signature::{SignatureEncoding, Signer};
+
+    const CA_PEM: &str = include_str!("../tests/resources/ca.pem");
+    const CLIENT_PEM: &str = include_str!("../tests/resources/client.pem");
+    const RSA_KEY: &str = include_str!("../tests/resources/jwt-rsa.key");
+
+    fn certificate(pem: &str) -> Certificate {
+        Certificate::parse(&encoding::certificate_der(pem).unwrap().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn verifies_ecdsa_signatures() {
+        let ca = certificate(CA_PEM);
+        let client = certificate(CLIENT_PEM);
+        let signed = Signed::parse(&client.der).unwrap();
+
+        assert_eq!(signed.algorithm, ECDSA_WITH_SHA256);
+        assert_eq!(signed.verify(&ca.subject_public_key_info), Ok(()));
+        assert_eq!(
+            signed.verify(&client.subject_public_key_info),
+            Err(SignatureError::Mismatch)
+        );
+
+        let mut tampered = client.der.clone();
+        let position = tampered.windows(5).position(|window| window == b"Joker").unwrap();
+        tampered[position] = b'j';
+        assert_eq!(
+            Signed::parse(&tampered).unwrap().verify(&ca.subject_public_key_info),
+            Err(SignatureError::Mismatch)
+        );
+    }
+
+    #[test]
+    fn verifies_rsa_signatures() {
+        let key = rsa::RsaPrivateKey::from_pkcs8_pem(RSA_KEY).unwrap();
+        let public_key_info = key.to_public_key().to_public_key_der().unwrap();
+        let tbs = der::encode(der::INTEGER, &[0x2a]);
+        let message = der::encode(SEQUENCE, &tbs);
+
+        for (algorithm, signature) in [
+            (
+                SHA256_WITH_RSA_ENCRYPTION,
+                rsa::pkcs1v15::SigningKey::<Sha256>::new(key.clone())
+                    .sign(&message)
+                    .to_vec(),
+            ),
+            (
+                SHA384_WITH_RSA_ENCRYPTION,
+                rsa::pkcs1v15::SigningKey::<Sha384>::new(key.clone())
+                    .sign(&message)
+                    .to_vec(),
+            ),
+            (
+                SHA512_WITH_RSA_ENCRYPTION,
+                rsa::pkcs1v15::SigningKey::<Sha512>::new(key.clone())
+                    .sign(&message)
+                    .to_vec(),
+            ),
+        ] {
+            let mut signed = Signed {
+                tbs: &tbs,
+                algorithm: algorithm.to_string(),
+                signature: &signature,
+            };
+            assert_eq!(signed.verify(public_key_info.as_bytes()), Ok(()));
+
+            // The digest is part of the signature
+            signed.algorithm = if algorithm == SHA256_WITH_RSA_ENCRYPTION {
+                SHA512_WITH_RSA_ENCRYPTION.to_string()
+            } else {
+                SHA256_WITH_RSA_ENCRYPTION.to_string()
+            };
+            assert_eq!(signed.verify(public_key_info.as_bytes()), Err(SignatureError::Mismatch));
+
+            signed.algorithm = ECDSA_WITH_SHA256.to_string();
+            assert_eq!(
+                signed.verify(public_key_info.as_bytes()),
+                Err(SignatureError::UnsupportedAlgorithm(ECDSA_WITH_SHA256.to_string()))
+            );
+        }
+    }
+
+    #[test]
+    fn verifies_p384_signatures() {
+        let key = p384::ecdsa::SigningKey::from_slice(&[0x2a; 48]).unwrap();
+        let point = key.verifying_key().to_encoded_point(false);
+        let key_algorithm = [
+            der::encode(OBJECT_IDENTIFIER, &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01]),
+            der::encode(OBJECT_IDENTIFIER, &[0x2b, 0x81, 0x04, 0x00, 0x22]),
+        ]
+        .concat();
+        let public_key_info = der::encode(
+            SEQUENCE,
+            &[
+                der::encode(SEQUENCE, &key_algorithm),
+                der::encode(BIT_STRING, &[&[0], point.as_bytes()].concat()),
+            ]
+            .concat(),
+        );
+        let tbs = der::encode(der::INTEGER, &[0x2a]);
+        let message = der::encode(SEQUENCE, &tbs);
+
+        for (algorithm, digest) in [
+            (ECDSA_WITH_SHA256, Sha256::digest(&message).to_vec()),
+            (ECDSA_WITH_SHA384, Sha384::digest(&message).to_vec()),
+            (ECDSA_WITH_SHA512, Sha512::digest(&message).to_vec()),
+        ] {
+            let signature: p384::ecdsa::Signature = key.sign_prehash(&digest).unwrap();
+            let signature = signature.to_der();
+            let mut signed = Signed {
+                tbs: &tbs,
+                algorithm: algorithm.to_string(),
+                signature: signature.as_bytes(),
+            };
+            assert_eq!(signed.verify(&public_key_info), Ok(()));
+
+            signed.tbs = &message;
+            assert_eq!(signed.verify(&public_key_info), Err(SignatureError::Mismatch));
+        }
+
+        let client = certificate(CLIENT_PEM);
+        let signed = Signed {
+            tbs: &[],
+            algorithm: SHA384_WITH_RSA_ENCRYPTION.to_string(),
+            signature: &[],
+        };
+        assert_eq!(
+            signed.verify(&client.subject_public_key_info),
+            Err(SignatureError::UnsupportedAlgorithm(
+                SHA384_WITH_RSA_ENCRYPTION.to_string()
+            ))
+        );
+    }
+}
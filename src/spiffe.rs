@@ -0,0 +1,204 @@
+// Copyright 2023 Salesforce, Inc. All rights reserved.
+use std::fmt;
+
+const SCHEME: &str = "spiffe://";
+const MAX_LENGTH: usize = 2048;
+
+/// SPIFFE ID as defined by the SPIFFE ID specification, `spiffe://<trust domain>/<path>`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SpiffeId {
+    /// The full ID.
+    pub id: String,
+    pub trust_domain: String,
+    /// Path of the ID starting with `/`, empty for the ID of the trust domain itself.
+    pub path: String,
+}
+
+/// Reasons a URI is not a valid SPIFFE ID.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SpiffeIdError {
+    WrongScheme,
+    TooLong,
+    EmptyTrustDomain,
+    InvalidTrustDomainCharacter,
+    EmptySegment,
+    DotSegment,
+    InvalidPathCharacter,
+    TrailingSlash,
+}
+
+impl fmt::Display for SpiffeIdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            SpiffeIdError::WrongScheme => "scheme is not spiffe",
+            SpiffeIdError::TooLong => "longer than 2048 bytes",
+            SpiffeIdError::EmptyTrustDomain => "trust domain is missing",
+            SpiffeIdError::InvalidTrustDomainCharacter => {
+                "trust domain characters are limited to lowercase letters, numbers, dots, dashes, and underscores"
+            }
+            SpiffeIdError::EmptySegment => "path cannot contain empty segments",
+            SpiffeIdError::DotSegment => "path cannot contain dot segments",
+            SpiffeIdError::InvalidPathCharacter => {
+                "path segment characters are limited to letters, numbers, dots, dashes, and underscores"
+            }
+            SpiffeIdError::TrailingSlash => "path cannot have a trailing slash",
+        };
+        f.write_str(message)
+    }
+}
+
+impl SpiffeId {
+    /// Parses and validates a SPIFFE ID.
+    pub fn parse(id: &str) -> Result<SpiffeId, SpiffeIdError> {
+        let rest = id.strip_prefix(SCHEME).ok_or(SpiffeIdError::WrongScheme)?;
+        if id.len() > MAX_LENGTH {
+            return Err(SpiffeIdError::TooLong);
+        }
+
+        let (trust_domain, path) = match rest.find('/') {
+            Some(index) => rest.split_at(index),
+            None => (rest, ""),
+        };
+
+        if trust_domain.is_empty() {
+            return Err(SpiffeIdError::EmptyTrustDomain);
+        }
+        if !trust_domain.bytes().all(is_trust_domain_byte) {
+            return Err(SpiffeIdError::InvalidTrustDomainCharacter);
+        }
+
+        if !path.is_empty() {
+            if path.ends_with('/') {
+                return Err(SpiffeIdError::TrailingSlash);
+            }
+            for segment in path[1..].split('/') {
+                match segment {
+                    "" => return Err(SpiffeIdError::EmptySegment),
+                    "." | ".." => return Err(SpiffeIdError::DotSegment),
+                    _ if !segment.bytes().all(is_path_byte) => return Err(SpiffeIdError::InvalidPathCharacter),
+                    _ => {}
+                }
+            }
+        }
+
+        Ok(SpiffeId {
+            id: id.to_string(),
+            trust_domain: trust_domain.to_string(),
+            path: path.to_string(),
+        })
+    }
+}
+
+/// Finds the SPIFFE ID among the URI SANs of a certificate.
+///
+/// An X.509-SVID carries exactly one SPIFFE ID, so several `spiffe://` URIs, or an invalid one, are an error.
+pub fn from_uri_sans(uri_sans: &[String]) -> Result<Option<SpiffeId>, String> {
+    let mut ids = uri_sans.iter().filter(|uri| {
+        uri.get(..SCHEME.len())
+            .is_some_and(|scheme| scheme.eq_ignore_ascii_case(SCHEME))
+    });
+
+    let id = match ids.next() {
+        Some(id) => id,
+        None => return Ok(None),
+    };
+    if ids.next().is_some() {
+        return Err("Peer cert has more than one SPIFFE ID".to_string());
+    }
+
+    SpiffeId::parse(id)
+        .map(Some)
+        .map_err(|err| format!("Invalid SPIFFE ID '{}' in peer cert: {}", id, err))
+}
+
+fn is_trust_domain_byte(byte: u8) -> bool {
+    byte.is_ascii_lowercase() || byte.is_ascii_digit() || byte == b'.' || byte == b'-' || byte == b'_'
+}
+
+fn is_path_byte(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || byte == b'.' || byte == b'-' || byte == b'_'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_workload_id() {
+        let id = SpiffeId::parse("spiffe://prod.example.org/ns/payments/sa/api").unwrap();
+
+        assert_eq!(id.id, "spiffe://prod.example.org/ns/payments/sa/api");
+        assert_eq!(id.trust_domain, "prod.example.org");
+        assert_eq!(id.path, "/ns/payments/sa/api");
+    }
+
+    #[test]
+    fn parses_trust_domain_id() {
+        let id = SpiffeId::parse("spiffe://example.org").unwrap();
+
+        assert_eq!(id.trust_domain, "example.org");
+        assert_eq!(id.path, "");
+    }
+
+    #[test]
+    fn rejects_invalid_ids() {
+        assert_eq!(
+            SpiffeId::parse("https://example.org/a"),
+            Err(SpiffeIdError::WrongScheme)
+        );
+        assert_eq!(SpiffeId::parse("spiffe:///ns/a"), Err(SpiffeIdError::EmptyTrustDomain));
+        assert_eq!(
+            SpiffeId::parse("spiffe://Example.org/a"),
+            Err(SpiffeIdError::InvalidTrustDomainCharacter)
+        );
+        assert_eq!(
+            SpiffeId::parse("spiffe://example.org:8443/a"),
+            Err(SpiffeIdError::InvalidTrustDomainCharacter)
+        );
+        assert_eq!(
+            SpiffeId::parse("spiffe://user@example.org/a"),
+            Err(SpiffeIdError::InvalidTrustDomainCharacter)
+        );
+        assert_eq!(
+            SpiffeId::parse("spiffe://example.org/ns//a"),
+            Err(SpiffeIdError::EmptySegment)
+        );
+        assert_eq!(
+            SpiffeId::parse("spiffe://example.org/ns/../a"),
+            Err(SpiffeIdError::DotSegment)
+        );
+        assert_eq!(
+            SpiffeId::parse("spiffe://example.org/ns/a?x=1"),
+            Err(SpiffeIdError::InvalidPathCharacter)
+        );
+        assert_eq!(
+            SpiffeId::parse("spiffe://example.org/ns/a/"),
+            Err(SpiffeIdError::TrailingSlash)
+        );
+        assert_eq!(
+            SpiffeId::parse(&format!("spiffe://example.org/{}", "a".repeat(2048))),
+            Err(SpiffeIdError::TooLong)
+        );
+    }
+
+    #[test]
+    fn finds_single_id_in_uri_sans() {
+        let uri_sans = vec![
+            "https://example.org".to_string(),
+            "spiffe://example.org/api".to_string(),
+        ];
+
+        assert_eq!(from_uri_sans(&uri_sans).unwrap().unwrap().path, "/api");
+        assert_eq!(from_uri_sans(&uri_sans[..1]), Ok(None));
+    }
+
+    #[test]
+    fn rejects_several_ids_in_uri_sans() {
+        let uri_sans = vec![
+            "spiffe://example.org/a".to_string(),
+            "spiffe://example.org/b".to_string(),
+        ];
+
+        assert!(from_uri_sans(&uri_sans).is_err());
+    }
+}
@@ -0,0 +1,276 @@
+// Copyright 2023 Salesforce, Inc. All rights reserved.
+use crate::dn::{self, DnError, Rdn};
+use crate::settings::Attribute;
+
+/// Standard subject attribute types: the OID and the names each type can be written with.
+#[rustfmt::skip]
+const ATTRIBUTE_TYPES: &[(Attribute, &str, &[&str])] = &[
+    (Attribute::Name, "2.5.4.3", &["CN", "commonName"]),
+    (Attribute::Surname, "2.5.4.4", &["SN", "surname"]),
+    (Attribute::SerialNumber, "2.5.4.5", &["SERIALNUMBER", "serialNumber"]),
+    (Attribute::Country, "2.5.4.6", &["C", "countryName"]),
+    (Attribute::Locality, "2.5.4.7", &["L", "localityName"]),
+    (Attribute::State, "2.5.4.8", &["ST", "S", "stateOrProvinceName"]),
+    (Attribute::Street, "2.5.4.9", &["STREET", "streetAddress"]),
+    (Attribute::Organization, "2.5.4.10", &["O", "organizationName"]),
+    (Attribute::OrganizationUnit, "2.5.4.11", &["OU", "organizationalUnitName"]),
+    (Attribute::Title, "2.5.4.12", &["title"]),
+    (Attribute::BusinessCategory, "2.5.4.15", &["businessCategory"]),
+    (Attribute::PostalCode, "2.5.4.17", &["postalCode"]),
+    (Attribute::GivenName, "2.5.4.42", &["GN", "givenName"]),
+    (Attribute::Initials, "2.5.4.43", &["initials"]),
+    (Attribute::GenerationQualifier, "2.5.4.44", &["generationQualifier"]),
+    (Attribute::DnQualifier, "2.5.4.46", &["dnQualifier"]),
+    (Attribute::Pseudonym, "2.5.4.65", &["pseudonym"]),
+    (Attribute::OrganizationIdentifier, "2.5.4.97", &["organizationIdentifier"]),
+    (Attribute::UserId, "0.9.2342.19200300.100.1.1", &["UID", "userId"]),
+    (Attribute::DomainComponent, "0.9.2342.19200300.100.1.25", &["DC", "domainComponent"]),
+    (Attribute::Email, "1.2.840.113549.1.9.1", &["emailAddress", "E", "email"]),
+];
+
+/// Issuer attributes and the subject attribute holding the same attribute type.
+#[rustfmt::skip]
+pub const ISSUER_ATTRIBUTES: &[(Attribute, Attribute)] = &[
+    (Attribute::IssuerName, Attribute::Name),
+    (Attribute::IssuerEmail, Attribute::Email),
+    (Attribute::IssuerOrganization, Attribute::Organization),
+    (Attribute::IssuerOrganizationUnit, Attribute::OrganizationUnit),
+    (Attribute::IssuerOrganizationIdentifier, Attribute::OrganizationIdentifier),
+    (Attribute::IssuerDomainComponent, Attribute::DomainComponent),
+    (Attribute::IssuerSerialNumber, Attribute::SerialNumber),
+    (Attribute::IssuerLocality, Attribute::Locality),
+    (Attribute::IssuerState, Attribute::State),
+    (Attribute::IssuerCountry, Attribute::Country),
+];
+
+/// Struct that contains the data we are interested in extracted from the subject field.
+///
+/// Attributes can be repeated in a subject, every field keeps all the values in the order they appear.
+#[derive(Default)]
+pub struct Subject {
+    pub name: Vec<String>,
+    pub surname: Vec<String>,
+    pub given_name: Vec<String>,
+    pub initials: Vec<String>,
+    pub generation_qualifier: Vec<String>,
+    pub pseudonym: Vec<String>,
+    pub title: Vec<String>,
+    pub email: Vec<String>,
+    pub user_id: Vec<String>,
+    pub serial_number: Vec<String>,
+    pub dn_qualifier: Vec<String>,
+    pub organization: Vec<String>,
+    pub organization_unit: Vec<String>,
+    pub organization_identifier: Vec<String>,
+    pub business_category: Vec<String>,
+    pub domain_component: Vec<String>,
+    pub street: Vec<String>,
+    pub postal_code: Vec<String>,
+    pub locality: Vec<String>,
+    pub state: Vec<String>,
+    pub country: Vec<String>,
+    /// Attributes of types missing from the standard table, as `(type, value)` pairs.
+    pub other: Vec<(String, String)>,
+}
+
+/// Maps an attribute to the field of the subject holding it, borrowed with the given reference kind, and returns
+/// `None` from the enclosing function for attributes that are not part of the subject.
+macro_rules! subject_field {
+    ($subject:expr, $attribute:expr, $($reference:tt)+) => {
+        match $attribute {
+            Attribute::Name => $($reference)+ $subject.name,
+            Attribute::Surname => $($reference)+ $subject.surname,
+            Attribute::GivenName => $($reference)+ $subject.given_name,
+            Attribute::Initials => $($reference)+ $subject.initials,
+            Attribute::GenerationQualifier => $($reference)+ $subject.generation_qualifier,
+            Attribute::Pseudonym => $($reference)+ $subject.pseudonym,
+            Attribute::Title => $($reference)+ $subject.title,
+            Attribute::Email => $($reference)+ $subject.email,
+            Attribute::UserId => $($reference)+ $subject.user_id,
+            Attribute::SerialNumber => $($reference)+ $subject.serial_number,
+            Attribute::DnQualifier => $($reference)+ $subject.dn_qualifier,
+            Attribute::Organization => $($reference)+ $subject.organization,
+            Attribute::OrganizationUnit => $($reference)+ $subject.organization_unit,
+            Attribute::OrganizationIdentifier => $($reference)+ $subject.organization_identifier,
+            Attribute::BusinessCategory => $($reference)+ $subject.business_category,
+            Attribute::DomainComponent => $($reference)+ $subject.domain_component,
+            Attribute::Street => $($reference)+ $subject.street,
+            Attribute::PostalCode => $($reference)+ $subject.postal_code,
+            Attribute::Locality => $($reference)+ $subject.locality,
+            Attribute::State => $($reference)+ $subject.state,
+            Attribute::Country => $($reference)+ $subject.country,
+            _ => return None,
+        }
+    };
+}
+
+impl Subject {
+    /// Returns the field holding the given attribute, `None` for attributes that are not part of the subject.
+    pub fn field(&self, attribute: Attribute) -> Option<&Vec<String>> {
+        Some(subject_field!(self, attribute, &))
+    }
+
+    fn field_mut(&mut self, attribute: Attribute) -> Option<&mut Vec<String>> {
+        Some(subject_field!(self, attribute, &mut))
+    }
+}
+
+/// Struct that contains the data extracted from the issuer field.
+///
+/// The issuer is a distinguished name like the subject, so it keeps the same fields.
+#[derive(Default)]
+pub struct Issuer {
+    pub names: Subject,
+}
+
+impl Issuer {
+    /// Returns the field holding the given issuer attribute, `None` for attributes that are not part of the issuer.
+    pub fn field(&self, attribute: Attribute) -> Option<&Vec<String>> {
+        ISSUER_ATTRIBUTES
+            .iter()
+            .find(|(issuer_attribute, _)| *issuer_attribute == attribute)
+            .and_then(|(_, subject_attribute)| self.names.field(*subject_attribute))
+    }
+}
+
+/// This function finds the subject attribute for an attribute type written as a name or an OID.
+pub fn attribute_for_type(attribute_type: &str) -> Option<Attribute> {
+    ATTRIBUTE_TYPES
+        .iter()
+        .find(|(_, oid, names)| {
+            *oid == attribute_type || names.iter().any(|name| name.eq_ignore_ascii_case(attribute_type))
+        })
+        .map(|(attribute, _, _)| *attribute)
+}
+
+/// This function extracts the name, email, and additional attributes from the given subject field.
+pub fn parse_subject(subject_field: &str) -> Result<Subject, DnError> {
+    Ok(subject_from_rdns(&dn::parse(subject_field)?))
+}
+
+/// This function extracts the attributes from RDNs, given in the order of the string representation of the name.
+pub fn subject_from_rdns<'a>(rdns: impl IntoIterator<Item = &'a Rdn>) -> Subject {
+    let mut subject = Subject::default();
+
+    for rdn in rdns {
+        for attribute in &rdn.attributes {
+            match attribute_for_type(&attribute.attribute_type).and_then(|known| subject.field_mut(known)) {
+                Some(field) => field.push(attribute.value.clone()),
+                None => subject
+                    .other
+                    .push((attribute.attribute_type.clone(), attribute.value.clone())),
+            }
+        }
+    }
+
+    subject
+}
+
+/// This function writes RDNs as an RFC 4514 string, using the short names of the standard attribute types.
+pub fn format_name<'a>(rdns: impl IntoIterator<Item = &'a Rdn>) -> String {
+    rdns.into_iter()
+        .map(|rdn| {
+            rdn.attributes
+                .iter()
+                .map(|attribute| {
+                    let attribute_type = ATTRIBUTE_TYPES
+                        .iter()
+                        .find(|(_, oid, _)| *oid == attribute.attribute_type)
+                        .map(|(_, _, names)| names[0])
+                        .unwrap_or(&attribute.attribute_type);
+                    format!("{}={}", attribute_type, dn::escape_value(&attribute.value))
+                })
+                .collect::<Vec<_>>()
+                .join("+")
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// This function extracts the attributes from the RDNs of the issuer, given in the order of the string representation.
+pub fn issuer_from_rdns<'a>(rdns: impl IntoIterator<Item = &'a Rdn>) -> Issuer {
+    Issuer {
+        names: subject_from_rdns(rdns),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_attribute_types_exactly() {
+        let subject = parse_subject("STREET=1 Main St,ST=CA,OU=Billing,O=ACME,L=Springfield,DC=example").unwrap();
+
+        assert_eq!(subject.street, vec!["1 Main St"]);
+        assert_eq!(subject.state, vec!["CA"]);
+        assert_eq!(subject.organization_unit, vec!["Billing"]);
+        assert_eq!(subject.organization, vec!["ACME"]);
+        assert_eq!(subject.locality, vec!["Springfield"]);
+        assert_eq!(subject.domain_component, vec!["example"]);
+    }
+
+    #[test]
+    fn matches_names_case_insensitively_and_oids() {
+        let subject = parse_subject("cn=Joker,2.5.4.10=Phantom Thieves,E=joker@example.com,SERIALNUMBER=42").unwrap();
+
+        assert_eq!(subject.name, vec!["Joker"]);
+        assert_eq!(subject.organization, vec!["Phantom Thieves"]);
+        assert_eq!(subject.email, vec!["joker@example.com"]);
+        assert_eq!(subject.serial_number, vec!["42"]);
+    }
+
+    #[test]
+    fn keeps_repeated_attributes_in_order() {
+        let subject = parse_subject("CN=Joker,OU=Billing,OU=Payments+OU=EMEA,DC=corp,DC=example,DC=com").unwrap();
+
+        assert_eq!(subject.name, vec!["Joker"]);
+        assert_eq!(subject.organization_unit, vec!["Billing", "Payments", "EMEA"]);
+        assert_eq!(subject.domain_component, vec!["corp", "example", "com"]);
+    }
+
+    #[test]
+    fn keeps_unknown_attribute_types() {
+        let subject = parse_subject("CN=Joker,1.3.6.1.4.1.99999.1=tenant-a,CNX=lookalike").unwrap();
+
+        assert_eq!(subject.name, vec!["Joker"]);
+        assert_eq!(
+            subject.other,
+            vec![
+                ("1.3.6.1.4.1.99999.1".to_string(), "tenant-a".to_string()),
+                ("CNX".to_string(), "lookalike".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn maps_issuer_attributes() {
+        let issuer = Issuer {
+            names: parse_subject("CN=Internal CA 2,O=ACME,C=US").unwrap(),
+        };
+
+        assert_eq!(
+            issuer.field(Attribute::IssuerName),
+            Some(&vec!["Internal CA 2".to_string()])
+        );
+        assert_eq!(
+            issuer.field(Attribute::IssuerOrganization),
+            Some(&vec!["ACME".to_string()])
+        );
+        assert_eq!(issuer.field(Attribute::IssuerCountry), Some(&vec!["US".to_string()]));
+        assert_eq!(issuer.field(Attribute::Name), None);
+    }
+
+    #[test]
+    fn formats_names() {
+        let rdns = dn::parse("CN=Joker\\, Jr.,OU=Billing+OU=Payments,1.3.6.1.4.1.99999.1=tenant-a").unwrap();
+        let oids: Vec<Rdn> =
+            dn::parse("2.5.4.3=Joker\\, Jr.,2.5.4.11=Billing+2.5.4.11=Payments,1.3.6.1.4.1.99999.1=tenant-a").unwrap();
+
+        assert_eq!(
+            format_name(&oids),
+            "CN=Joker\\, Jr.,OU=Billing+OU=Payments,1.3.6.1.4.1.99999.1=tenant-a"
+        );
+        assert_eq!(subject_from_rdns(&rdns).organization_unit, vec!["Billing", "Payments"]);
+    }
+}
@@ -0,0 +1,510 @@
+// Copyright 2023 Salesforce, Inc. All rights reserved.
+use crate::der::{
+    self, DerError, Reader, BIT_STRING, BOOLEAN, CONTEXT_0, CONTEXT_3, INTEGER, OBJECT_IDENTIFIER, OCTET_STRING,
+    SEQUENCE, SET,
+};
+use crate::dn::{AttributeTypeAndValue, Rdn};
+use crate::encoding;
+use std::convert::TryInto;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+const SUBJECT_ALTERNATIVE_NAME: &str = "2.5.29.17";
+const KEY_USAGE: &str = "2.5.29.15";
+const EXTENDED_KEY_USAGE: &str = "2.5.29.37";
+const CERTIFICATE_POLICIES: &str = "2.5.29.32";
+
+/// Nesting depth of the sequences and sets decoded in extension values, deeper values are rejected.
+const MAX_VALUE_DEPTH: usize = 16;
+
+/// Extended key usage purposes from RFC 5280, by name and OID.
+pub const EXTENDED_KEY_USAGES: &[(&str, &str)] = &[
+    ("serverAuth", "1.3.6.1.5.5.7.3.1"),
+    ("clientAuth", "1.3.6.1.5.5.7.3.2"),
+    ("codeSigning", "1.3.6.1.5.5.7.3.3"),
+    ("emailProtection", "1.3.6.1.5.5.7.3.4"),
+    ("timeStamping", "1.3.6.1.5.5.7.3.8"),
+    ("OCSPSigning", "1.3.6.1.5.5.7.3.9"),
+    ("anyExtendedKeyUsage", "2.5.29.37.0"),
+];
+
+/// GeneralName tags of the subject alternative names the policy exposes.
+const RFC822_NAME: u8 = 0x81;
+const DNS_NAME: u8 = 0x82;
+const URI: u8 = 0x86;
+const IP_ADDRESS: u8 = 0x87;
+
+/// Validity window of a certificate, in seconds since the Unix epoch.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Validity {
+    pub not_before: i64,
+    pub not_after: i64,
+}
+
+impl Validity {
+    /// Whole days left before the certificate expires, negative once it expired.
+    pub fn days_until_expiry(&self, now: i64) -> i64 {
+        (self.not_after - now).div_euclid(86_400)
+    }
+}
+
+/// Subject alternative names, in the order they appear in the extension.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SubjectAlternativeNames {
+    pub dns_names: Vec<String>,
+    pub ip_addresses: Vec<String>,
+    pub email_addresses: Vec<String>,
+    pub uris: Vec<String>,
+}
+
+/// Key usage bits of a certificate, bit 0 being `digitalSignature`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeyUsage(pub u16);
+
+impl KeyUsage {
+    pub const DIGITAL_SIGNATURE: u16 = 1 << 0;
+    pub const KEY_AGREEMENT: u16 = 1 << 4;
+
+    pub fn contains(self, bits: u16) -> bool {
+        self.0 & bits == bits
+    }
+}
+
+/// A certificate extension, with its value left encoded.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Extension {
+    /// Dotted OID of the extension, such as `2.5.29.17`.
+    pub oid: String,
+    pub critical: bool,
+    /// DER encoded value, the contents of the `extnValue` octet string.
+    pub value: Vec<u8>,
+}
+
+/// X.509 certificate decoded from DER.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Certificate {
+    /// Version of the certificate, 3 for X.509 v3.
+    pub version: u8,
+    /// Serial number as lowercase hex.
+    pub serial_number: String,
+    /// RDNs of the issuer in encoding order, the reverse of the string representation.
+    pub issuer: Vec<Rdn>,
+    /// DER encoded issuer name.
+    pub issuer_der: Vec<u8>,
+    pub validity: Validity,
+    /// RDNs of the subject in encoding order, the reverse of the string representation.
+    pub subject: Vec<Rdn>,
+    /// DER encoded SubjectPublicKeyInfo.
+    pub subject_public_key_info: Vec<u8>,
+    pub subject_alternative_names: SubjectAlternativeNames,
+    pub extensions: Vec<Extension>,
+    /// DER encoding of the whole certificate.
+    pub der: Vec<u8>,
+}
+
+impl Certificate {
+    /// Decodes a DER encoded certificate. The signature is not verified, that is left to the TLS handshake.
+    pub fn parse(certificate_der: &[u8]) -> Result<Certificate, DerError> {
+        let mut certificate = Reader::new(Reader::new(certificate_der).read_tag(SEQUENCE)?);
+        let mut tbs_certificate = Reader::new(certificate.read_tag(SEQUENCE)?);
+
+        let version = match tbs_certificate.read_optional(CONTEXT_0)? {
+            Some(version) => match Reader::new(version).read_tag(INTEGER)? {
+                [version] if *version <= 2 => version + 1,
+                _ => return Err(DerError::InvalidLength),
+            },
+            None => 1,
+        };
+        let serial_number = serial_number(tbs_certificate.read_tag(INTEGER)?);
+        // Signature algorithm, repeated outside of the signed part
+        tbs_certificate.read_tag(SEQUENCE)?;
+        let issuer_der = tbs_certificate.read_tag(SEQUENCE)?;
+        let issuer = parse_name(issuer_der)?;
+
+        let mut validity = Reader::new(tbs_certificate.read_tag(SEQUENCE)?);
+        let validity = Validity {
+            not_before: validity.read_time()?,
+            not_after: validity.read_time()?,
+        };
+
+        let subject = parse_name(tbs_certificate.read_tag(SEQUENCE)?)?;
+        let subject_public_key_info = tbs_certificate.read()?;
+        if subject_public_key_info.tag != SEQUENCE {
+            return Err(DerError::UnexpectedTag {
+                expected: SEQUENCE,
+                found: subject_public_key_info.tag,
+            });
+        }
+
+        // Issuer and subject unique identifiers are skipped, extensions come last
+        let mut extensions = Vec::new();
+        while let Some(tag) = tbs_certificate.peek_tag() {
+            let element = tbs_certificate.read()?;
+            if tag == CONTEXT_3 {
+                extensions = parse_extensions(Reader::new(element.value).read_tag(SEQUENCE)?)?;
+            }
+        }
+
+        let mut certificate = Certificate {
+            version,
+            serial_number,
+            issuer,
+            issuer_der: der::encode(SEQUENCE, issuer_der),
+            validity,
+            subject,
+            subject_public_key_info: der::encode(SEQUENCE, subject_public_key_info.value),
+            subject_alternative_names: SubjectAlternativeNames::default(),
+            extensions,
+            der: certificate_der.to_vec(),
+        };
+        if let Some(extension) = certificate.extension(SUBJECT_ALTERNATIVE_NAME) {
+            certificate.subject_alternative_names = parse_subject_alternative_names(&extension.value)?;
+        }
+        Ok(certificate)
+    }
+
+    /// Returns the extension with the given OID.
+    pub fn extension(&self, oid: &str) -> Option<&Extension> {
+        self.extensions.iter().find(|extension| extension.oid == oid)
+    }
+
+    /// Returns the key usage bits, `None` when the certificate has no key usage extension.
+    pub fn key_usage(&self) -> Result<Option<KeyUsage>, DerError> {
+        let extension = match self.extension(KEY_USAGE) {
+            Some(extension) => extension,
+            None => return Ok(None),
+        };
+        // The first byte of a bit string counts the unused bits of the last byte
+        let bits = match Reader::new(&extension.value).read_tag(BIT_STRING)? {
+            [_, bytes @ ..] => bytes,
+            [] => return Err(DerError::InvalidLength),
+        };
+        let usage = bits.iter().take(2).enumerate().fold(0u16, |usage, (index, byte)| {
+            usage | (byte.reverse_bits() as u16) << (8 * index)
+        });
+        Ok(Some(KeyUsage(usage)))
+    }
+
+    /// Returns the OIDs of the extended key usages, `None` when the certificate has no extended key usage extension.
+    pub fn extended_key_usages(&self) -> Result<Option<Vec<String>>, DerError> {
+        let extension = match self.extension(EXTENDED_KEY_USAGE) {
+            Some(extension) => extension,
+            None => return Ok(None),
+        };
+        let mut purposes = Reader::new(Reader::new(&extension.value).read_tag(SEQUENCE)?);
+        let mut oids = Vec::new();
+        while !purposes.is_empty() {
+            oids.push(der::object_identifier(purposes.read_tag(OBJECT_IDENTIFIER)?)?);
+        }
+        Ok(Some(oids))
+    }
+
+    /// Returns the values of the extension with the given OID, `None` when the certificate does not have it.
+    /// Certificate policies are reduced to the policy OIDs, other extensions to the primitive values they hold.
+    pub fn extension_values(&self, oid: &str) -> Result<Option<Vec<String>>, DerError> {
+        let extension = match self.extension(oid) {
+            Some(extension) => extension,
+            None => return Ok(None),
+        };
+        let mut values = Vec::new();
+        if oid == CERTIFICATE_POLICIES {
+            values = certificate_policies(&extension.value)?;
+        } else {
+            decode_values(&extension.value, &mut values, 0)?;
+        }
+        Ok(Some(values))
+    }
+}
+
+/// Returns the name of an extended key usage OID, or the OID itself when it has none.
+pub fn extended_key_usage_name(oid: &str) -> &str {
+    EXTENDED_KEY_USAGES
+        .iter()
+        .find(|(_, known)| *known == oid)
+        .map(|(name, _)| *name)
+        .unwrap_or(oid)
+}
+
+/// Writes the serial number as hex, without the leading zero byte keeping large serials positive.
+pub fn serial_number(value: &[u8]) -> String {
+    match value {
+        [0, rest @ ..] if !rest.is_empty() => encoding::hex(rest),
+        _ => encoding::hex(value),
+    }
+}
+
+/// This function decodes the RDN sequence of a name, keeping unknown value types hex encoded as in RFC 4514.
+pub fn parse_name(name: &[u8]) -> Result<Vec<Rdn>, DerError> {
+    let mut rdns = Vec::new();
+    let mut name = Reader::new(name);
+    while !name.is_empty() {
+        let mut set = Reader::new(name.read_tag(SET)?);
+        let mut attributes = Vec::new();
+        while !set.is_empty() {
+            let mut attribute = Reader::new(set.read_tag(SEQUENCE)?);
+            let attribute_type = der::object_identifier(attribute.read_tag(OBJECT_IDENTIFIER)?)?;
+            let value = attribute.read()?;
+            let value = der::string(value.tag, value.value)
+                .unwrap_or_else(|_| format!("#{}", encoding::hex(&der::encode(value.tag, value.value))));
+            attributes.push(AttributeTypeAndValue { attribute_type, value });
+        }
+        rdns.push(Rdn { attributes });
+    }
+    Ok(rdns)
+}
+
+fn parse_extensions(extensions: &[u8]) -> Result<Vec<Extension>, DerError> {
+    let mut parsed = Vec::new();
+    let mut extensions = Reader::new(extensions);
+    while !extensions.is_empty() {
+        let mut extension = Reader::new(extensions.read_tag(SEQUENCE)?);
+        let oid = der::object_identifier(extension.read_tag(OBJECT_IDENTIFIER)?)?;
+        let critical = extension
+            .read_optional(BOOLEAN)?
+            .is_some_and(|critical| critical.iter().any(|byte| *byte != 0));
+        let value = extension.read_tag(OCTET_STRING)?.to_vec();
+        parsed.push(Extension { oid, critical, value });
+    }
+    Ok(parsed)
+}
+
+fn parse_subject_alternative_names(value: &[u8]) -> Result<SubjectAlternativeNames, DerError> {
+    let mut names = SubjectAlternativeNames::default();
+    let mut general_names = Reader::new(Reader::new(value).read_tag(SEQUENCE)?);
+    while !general_names.is_empty() {
+        let name = general_names.read()?;
+        let text = || String::from_utf8(name.value.to_vec()).map_err(|_| DerError::InvalidString);
+        match name.tag {
+            DNS_NAME => names.dns_names.push(text()?),
+            RFC822_NAME => names.email_addresses.push(text()?),
+            URI => names.uris.push(text()?),
+            IP_ADDRESS => match name.value.len() {
+                4 => {
+                    let octets: [u8; 4] = name.value.try_into().map_err(|_| DerError::InvalidLength)?;
+                    names.ip_addresses.push(Ipv4Addr::from(octets).to_string())
+                }
+                16 => {
+                    let octets: [u8; 16] = name.value.try_into().map_err(|_| DerError::InvalidLength)?;
+                    names.ip_addresses.push(Ipv6Addr::from(octets).to_string())
+                }
+                _ => return Err(DerError::InvalidLength),
+            },
+            // Other names, directory names and the like are not exposed
+            _ => {}
+        }
+    }
+    Ok(names)
+}
+
+fn certificate_policies(value: &[u8]) -> Result<Vec<String>, DerError> {
+    let mut policies = Reader::new(Reader::new(value).read_tag(SEQUENCE)?);
+    let mut oids = Vec::new();
+    while !policies.is_empty() {
+        // Policy qualifiers follow the identifier, they are left out
+        let mut policy = Reader::new(policies.read_tag(SEQUENCE)?);
+        oids.push(der::object_identifier(policy.read_tag(OBJECT_IDENTIFIER)?)?);
+    }
+    Ok(oids)
+}
+
+/// This function collects the primitive values of DER elements, descending into sequences and sets.
+/// Values of unknown types are kept hex encoded, as in names.
+fn decode_values(data: &[u8], values: &mut Vec<String>, depth: usize) -> Result<(), DerError> {
+    if depth > MAX_VALUE_DEPTH {
+        return Err(DerError::TooDeep);
+    }
+    let mut elements = Reader::new(data);
+    while !elements.is_empty() {
+        let element = elements.read()?;
+        match element.tag {
+            SEQUENCE | SET => decode_values(element.value, values, depth + 1)?,
+            BOOLEAN => values.push(element.value.iter().any(|byte| *byte != 0).to_string()),
+            INTEGER => values.push(der::integer(element.value)?),
+            OBJECT_IDENTIFIER => values.push(der::object_identifier(element.value)?),
+            tag => values.push(
+                der::string(tag, element.value)
+                    .unwrap_or_else(|_| format!("#{}", encoding::hex(&der::encode(tag, element.value)))),
+            ),
+        }
+    }
+    Ok(())
+}
+
+/// Writes a time in seconds since the Unix epoch as an RFC 3339 UTC timestamp.
+pub fn format_time(seconds: i64) -> String {
+    let (year, month, day) = der::civil_from_days(seconds.div_euclid(86_400));
+    let time = seconds.rem_euclid(86_400);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        time / 3_600,
+        time % 3_600 / 60,
+        time % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CLIENT_PEM: &str = include_str!("../tests/resources/client.pem");
+
+    fn client_certificate() -> Certificate {
+        let der = encoding::certificate_der(CLIENT_PEM).unwrap().unwrap();
+        Certificate::parse(&der).unwrap()
+    }
+
+    fn attribute(attribute_type: &str, value: &str) -> AttributeTypeAndValue {
+        AttributeTypeAndValue {
+            attribute_type: attribute_type.to_string(),
+            value: value.to_string(),
+        }
+    }
+
+    #[test]
+    fn parses_certificate() {
+        let certificate = client_certificate();
+
+        assert_eq!(certificate.version, 3);
+        assert_eq!(certificate.serial_number, "1a2b3c4d5e6f");
+        assert_eq!(format_time(certificate.validity.not_before), "2025-01-01T00:00:00Z");
+        assert_eq!(format_time(certificate.validity.not_after), "2125-01-01T00:00:00Z");
+        assert_eq!(
+            certificate.issuer,
+            vec![
+                Rdn {
+                    attributes: vec![attribute("2.5.4.6", "US")]
+                },
+                Rdn {
+                    attributes: vec![attribute("2.5.4.10", "ACME")]
+                },
+                Rdn {
+                    attributes: vec![attribute("2.5.4.3", "Internal CA 2")]
+                },
+            ]
+        );
+        assert_eq!(certificate.subject.len(), 6);
+        assert_eq!(
+            certificate.subject[3].attributes,
+            vec![attribute("2.5.4.11", "Billing"), attribute("2.5.4.11", "Payments")]
+        );
+        assert_eq!(certificate.subject_public_key_info[0], SEQUENCE);
+    }
+
+    #[test]
+    fn parses_subject_alternative_names() {
+        let names = client_certificate().subject_alternative_names;
+
+        assert_eq!(names.dns_names, vec!["joker.example.com", "api.example.com"]);
+        assert_eq!(names.ip_addresses, vec!["10.0.0.1", "2001:db8::1"]);
+        assert_eq!(names.email_addresses, vec!["joker@example.com"]);
+        assert_eq!(names.uris, vec!["spiffe://example.org/ns/payments/sa/api"]);
+    }
+
+    #[test]
+    fn keeps_extensions() {
+        let certificate = client_certificate();
+
+        let key_usage = certificate.extension("2.5.29.15").unwrap();
+        assert!(key_usage.critical);
+        let custom = certificate.extension("1.3.6.1.4.1.99999.1").unwrap();
+        assert!(!custom.critical);
+        assert_eq!(custom.value, b"\x0c\x08tenant-a");
+    }
+
+    #[test]
+    fn decodes_key_usages() {
+        let certificate = client_certificate();
+
+        let key_usage = certificate.key_usage().unwrap().unwrap();
+        // digitalSignature and keyEncipherment
+        assert_eq!(key_usage, KeyUsage(0b101));
+        assert!(key_usage.contains(KeyUsage::DIGITAL_SIGNATURE));
+        assert!(!key_usage.contains(KeyUsage::KEY_AGREEMENT));
+        assert_eq!(
+            certificate.extended_key_usages().unwrap(),
+            Some(vec!["1.3.6.1.5.5.7.3.2".to_string()])
+        );
+        assert_eq!(extended_key_usage_name("1.3.6.1.5.5.7.3.2"), "clientAuth");
+        assert_eq!(extended_key_usage_name("1.3.6.1.4.1.99999.2"), "1.3.6.1.4.1.99999.2");
+    }
+
+    #[test]
+    fn decodes_extension_values() {
+        let certificate = client_certificate();
+
+        assert_eq!(
+            certificate.extension_values("1.3.6.1.4.1.99999.1").unwrap(),
+            Some(vec!["tenant-a".to_string()])
+        );
+        assert_eq!(certificate.extension_values("1.3.6.1.4.1.99999.2").unwrap(), None);
+
+        let mut values = Vec::new();
+        let sequence = der::encode(
+            SEQUENCE,
+            &[
+                der::encode(INTEGER, &[0x2a]),
+                der::encode(OBJECT_IDENTIFIER, &[0x55, 0x04, 0x03]),
+                der::encode(0x80, b"x"),
+            ]
+            .concat(),
+        );
+        decode_values(&sequence, &mut values, 0).unwrap();
+        assert_eq!(values, vec!["42", "2.5.4.3", "#800178"]);
+    }
+
+    #[test]
+    fn rejects_deeply_nested_extension_values() {
+        let nested = |depth| (0..depth).fold(der::encode(INTEGER, &[0x2a]), |value, _| der::encode(SEQUENCE, &value));
+
+        assert_eq!(decode_values(&nested(MAX_VALUE_DEPTH), &mut Vec::new(), 0), Ok(()));
+        assert_eq!(
+            decode_values(&nested(MAX_VALUE_DEPTH + 1), &mut Vec::new(), 0),
+            Err(DerError::TooDeep)
+        );
+    }
+
+    #[test]
+    fn decodes_certificate_policy_oids() {
+        // anyPolicy with a CPS qualifier, then 2.23.140.1.2.1
+        let qualifier = der::encode(
+            SEQUENCE,
+            &[
+                der::encode(OBJECT_IDENTIFIER, &[0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x02, 0x01]),
+                der::encode(0x16, b"https://cps"),
+            ]
+            .concat(),
+        );
+        let policies = der::encode(
+            SEQUENCE,
+            &[
+                der::encode(
+                    SEQUENCE,
+                    &[
+                        der::encode(OBJECT_IDENTIFIER, &[0x55, 0x1d, 0x20, 0x00]),
+                        der::encode(SEQUENCE, &qualifier),
+                    ]
+                    .concat(),
+                ),
+                der::encode(
+                    SEQUENCE,
+                    &der::encode(OBJECT_IDENTIFIER, &[0x67, 0x81, 0x0c, 0x01, 0x02, 0x01]),
+                ),
+            ]
+            .concat(),
+        );
+
+        assert_eq!(
+            certificate_policies(&policies).unwrap(),
+            vec!["2.5.29.32.0", "2.23.140.1.2.1"]
+        );
+    }
+
+    #[test]
+    fn rejects_truncated_certificate() {
+        let der = client_certificate().der;
+
+        assert_eq!(Certificate::parse(&der[..der.len() - 10]), Err(DerError::Truncated));
+    }
+}
@@ -0,0 +1,141 @@
+// Copyright 2023 Salesforce, Inc. All rights reserved.
+
+/// Name of the XFCC header, in the format used by Envoy.
+pub const HEADER: &str = "x-forwarded-client-cert";
+
+/// What the policy does with the XFCC header, like Envoy's `forward_client_cert_details`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ForwardMode {
+    /// Removes the header.
+    Sanitize,
+    /// Forwards the header as sent when the client connection is mTLS.
+    ForwardOnly,
+    /// Appends the details of the client certificate to the header when the client connection is mTLS.
+    AppendForward,
+    /// Replaces the header with the details of the client certificate when the client connection is mTLS.
+    SanitizeSet,
+    /// Always forwards the header as sent.
+    AlwaysForwardOnly,
+}
+
+/// Optional details of the client certificate added to the XFCC element, like Envoy's
+/// `set_current_client_cert_details`. `By` and `Hash` are always added.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Detail {
+    Subject,
+    Cert,
+    Chain,
+    Uri,
+    Dns,
+}
+
+/// One XFCC element, describing a single hop.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Element {
+    pairs: Vec<(&'static str, String)>,
+}
+
+impl Element {
+    /// Adds a key/value pair, keys such as `URI` and `DNS` can be repeated.
+    pub fn push(&mut self, key: &'static str, value: &str) {
+        self.pairs.push((key, value.to_string()));
+    }
+
+    /// Writes the element with `;` separated pairs, quoting the values that need it.
+    pub fn to_header(&self) -> String {
+        self.pairs
+            .iter()
+            .map(|(key, value)| format!("{}={}", key, quote(key, value)))
+            .collect::<Vec<_>>()
+            .join(";")
+    }
+}
+
+/// Quotes values containing a separator or a double quote. Like Envoy, the subject is always quoted.
+fn quote(key: &str, value: &str) -> String {
+    let needs_quotes = key == "Subject" || value.contains([',', ';', '=', '"']);
+    if !needs_quotes {
+        return value.to_string();
+    }
+    format!("\"{}\"", value.replace('"', "\\\""))
+}
+
+/// Returns the XFCC header to send upstream, `None` to remove it.
+///
+/// `element` builds the element of the current client and is only called when the mode adds it.
+pub fn resolve(
+    mode: ForwardMode,
+    existing: Option<&str>,
+    mtls: bool,
+    element: impl FnOnce() -> Element,
+) -> Option<String> {
+    let existing = existing.filter(|value| !value.is_empty());
+    match mode {
+        ForwardMode::Sanitize => None,
+        ForwardMode::AlwaysForwardOnly => existing.map(str::to_string),
+        _ if !mtls => None,
+        ForwardMode::ForwardOnly => existing.map(str::to_string),
+        ForwardMode::AppendForward => {
+            let current = element().to_header();
+            Some(match existing {
+                Some(existing) => format!("{},{}", existing, current),
+                None => current,
+            })
+        }
+        ForwardMode::SanitizeSet => Some(element().to_header()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn element() -> Element {
+        let mut element = Element::default();
+        element.push("Hash", "abcd");
+        element.push("Subject", "CN=Joker,O=Acme\\, \"Inc\"");
+        element.push("URI", "spiffe://example.org/api");
+        element.push("DNS", "a.example.org");
+        element.push("DNS", "b.example.org");
+        element
+    }
+
+    #[test]
+    fn quotes_values() {
+        assert_eq!(
+            element().to_header(),
+            r#"Hash=abcd;Subject="CN=Joker,O=Acme\, \"Inc\"";URI=spiffe://example.org/api;DNS=a.example.org;DNS=b.example.org"#
+        );
+    }
+
+    #[test]
+    fn resolves_modes() {
+        let forwarded = "By=spiffe://example.org/edge;Hash=1234";
+        let existing = Some(forwarded);
+        let current = element().to_header();
+
+        assert_eq!(resolve(ForwardMode::Sanitize, existing, true, element), None);
+        assert_eq!(
+            resolve(ForwardMode::ForwardOnly, existing, true, element).as_deref(),
+            existing
+        );
+        assert_eq!(resolve(ForwardMode::ForwardOnly, existing, false, element), None);
+        assert_eq!(
+            resolve(ForwardMode::AppendForward, existing, true, element),
+            Some(format!("{},{}", forwarded, current))
+        );
+        assert_eq!(
+            resolve(ForwardMode::AppendForward, None, true, element),
+            Some(current.clone())
+        );
+        assert_eq!(
+            resolve(ForwardMode::SanitizeSet, existing, true, element),
+            Some(current)
+        );
+        assert_eq!(resolve(ForwardMode::SanitizeSet, existing, false, element), None);
+        assert_eq!(
+            resolve(ForwardMode::AlwaysForwardOnly, existing, false, element).as_deref(),
+            existing
+        );
+    }
+}
@@ -11,4 +11,113 @@ pub const COMMON_CONFIG_DIR: &str =  concat!(env!("CARGO_MANIFEST_DIR"), "/tests
 // In case the project name changes, override this value with the actual policy name.
 // To obtain the current name, run the "make show-policy-ref-name" goal, or read it from
 // "target/policy-ref-name.txt" after building the project.
-pub const POLICY_NAME: &str = "mtls-cert-v1-0-impl";
\ No newline at end of file
+pub const POLICY_NAME: &str = "mtls-cert-v1-0-impl";
+
+use httpmock::MockServer;
+use pdk_test::port::Port;
+use pdk_test::services::flex::{ApiConfig, Flex, FlexConfig, PolicyConfig};
+use pdk_test::services::httpmock::{HttpMock, HttpMockConfig};
+use pdk_test::TestComposite;
+
+// Flex port for the internal test network
+pub const FLEX_PORT: Port = 8081;
+
+// Certificates of the mTLS tests, all issued by the test CA.
+pub const CA_PEM: &str = include_str!("../resources/ca.pem");
+pub const SERVER_PEM: &str = include_str!("../resources/server.pem");
+pub const SERVER_KEY: &str = include_str!("../resources/server.key");
+pub const CLIENT_PEM: &str = include_str!("../resources/client.pem");
+pub const CLIENT_KEY: &str = include_str!("../resources/client.key");
+
+// A local-flex instance running the policy in front of a MockServer backend.
+// The composite is kept so the services live as long as the test.
+pub struct PolicyTest {
+    pub _composite: TestComposite,
+    pub flex_url: String,
+    pub backend: MockServer,
+}
+
+// Starts Flex with the policy and the given configuration, over plain HTTP.
+pub async fn start(configuration: serde_json::Value) -> anyhow::Result<PolicyTest> {
+    start_with_policies(vec![policy(configuration)], "http").await
+}
+
+// Starts Flex with the policy and the given configuration behind a TLS listener that requires client
+// certificates issued by the test CA, so the requests go through the mTLS path of the policy.
+pub async fn start_mtls(configuration: serde_json::Value) -> anyhow::Result<PolicyTest> {
+    let tls = PolicyConfig::builder()
+        .name("tls")
+        .configuration(serde_json::json!({
+            "certificate": {"crt": SERVER_PEM, "key": SERVER_KEY},
+            "trustedCA": CA_PEM,
+            "requireClientCertificate": true,
+        }))
+        .build();
+    start_with_policies(vec![tls, policy(configuration)], "https").await
+}
+
+// Client presenting the test client certificate and trusting the test CA.
+pub fn mtls_client() -> anyhow::Result<reqwest::Client> {
+    let identity = reqwest::Identity::from_pkcs8_pem(CLIENT_PEM.as_bytes(), CLIENT_KEY.as_bytes())?;
+    Ok(reqwest::Client::builder()
+        .identity(identity)
+        .add_root_certificate(reqwest::Certificate::from_pem(CA_PEM.as_bytes())?)
+        // The external URL of Flex is not the name in the server certificate
+        .danger_accept_invalid_hostnames(true)
+        .build()?)
+}
+
+fn policy(configuration: serde_json::Value) -> PolicyConfig {
+    PolicyConfig::builder()
+        .name(POLICY_NAME)
+        .configuration(configuration)
+        .build()
+}
+
+async fn start_with_policies(policies: Vec<PolicyConfig>, scheme: &str) -> anyhow::Result<PolicyTest> {
+
+    // Configure an HttpMock service
+    let httpmock_config = HttpMockConfig::builder()
+        .port(80)
+        .version("latest")
+        .hostname("backend")
+        .build();
+
+    let api_config = ApiConfig::builder()
+        .name("myApi")
+        .upstream(&httpmock_config)
+        .path("/anything/echo/")
+        .port(FLEX_PORT)
+        .policies(policies)
+        .build();
+
+    // Configure a Flex service
+    let flex_config = FlexConfig::builder()
+        .version("1.9.0")
+        .hostname("local-flex")
+        .with_api(api_config)
+        .config_mounts([
+            (POLICY_DIR, "policy"),
+            (COMMON_CONFIG_DIR, "common"),
+        ])
+        .build();
+
+    // Compose the services
+    let composite = TestComposite::builder()
+        .with_service(flex_config)
+        .with_service(httpmock_config)
+        .build()
+        .await?;
+
+    let flex: Flex = composite.service()?;
+    let flex_url = flex.external_url(FLEX_PORT).unwrap().to_string().replacen("http", scheme, 1);
+
+    let httpmock: HttpMock = composite.service()?;
+    let backend = MockServer::connect_async(httpmock.socket()).await;
+
+    Ok(PolicyTest {
+        _composite: composite,
+        flex_url,
+        backend,
+    })
+}
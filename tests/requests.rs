@@ -4,15 +4,11 @@ mod common;
 
 use httpmock::MockServer;
 use pdk_test::{pdk_test, TestComposite};
-use pdk_test::port::Port;
 use pdk_test::services::flex::{ApiConfig, FlexConfig, Flex, PolicyConfig};
 use pdk_test::services::httpmock::{HttpMockConfig, HttpMock};
 
 use common::*;
 
-// Flex port for the internal test network
-const FLEX_PORT: Port = 8081;
-
 // This integration test shows how to build a test to compose a local-flex instance
 // with a MockServer backend
 #[pdk_test]
@@ -27,7 +23,7 @@ async fn hello() -> anyhow::Result<()> {
 
     let policy_config = PolicyConfig::builder()
         .name(POLICY_NAME)
-        .configuration(serde_json::json!({"someProperty": "desiredValue"}))
+        .configuration(serde_json::json!({"failureMode": "annotate"}))
         .build();
 
     let api_config = ApiConfig::builder()
@@ -82,3 +78,168 @@ async fn hello() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+// Requests without a client certificate are rejected when the policy enforces mTLS
+#[pdk_test]
+async fn enforce_mode_rejects_missing_certificate() -> anyhow::Result<()> {
+    let test = start(serde_json::json!({"mode": "enforce"})).await?;
+
+    // Perform a plain HTTP request, without a client certificate
+    let response = reqwest::get(format!("{}/hello", test.flex_url)).await?;
+
+    // Assert on the problem details response
+    assert_eq!(response.status(), 401);
+    assert_eq!(response.headers()["content-type"], "application/problem+json");
+    let body = response.text().await?;
+    assert!(body.contains(r#""status":401"#));
+
+    Ok(())
+}
+
+// The attributes of the client certificate are forwarded to the backend over mTLS
+#[pdk_test]
+async fn mtls_request_forwards_certificate_attributes() -> anyhow::Result<()> {
+    let test = start_mtls(serde_json::json!({
+        "mode": "enforce",
+        "attributes": ["name", "organization", "sanDns", "fingerprintSha256", "certificateSerial"]
+    }))
+    .await?;
+
+    // Only answer when the attributes of the test client certificate were set. Envoy provides the first DNS SAN
+    // and the digest, but not the PEM the serial number is read from.
+    test.backend.mock_async(|when, then| {
+        when.path_contains("/hello")
+            .header("x-peer-certificate-present", "true")
+            .header("x-peer-name", "Joker")
+            .header("x-peer-organization", "Phantom Thieves")
+            .header("x-peer-san-dns", "joker.example.com")
+            .header(
+                "x-peer-fingerprint-sha256",
+                "8fe100a8888a14e162a55324a31c9c1a070d933814b72ca9a92011150d13c063",
+            )
+            .header_missing("x-peer-certificate-serial")
+            .header_missing("x-peer-certificate-errors");
+        then.status(202).body("World!");
+    }).await;
+
+    let response = mtls_client()?
+        .get(format!("{}/hello", test.flex_url))
+        .send()
+        .await?;
+
+    assert_eq!(response.status(), 202);
+
+    Ok(())
+}
+
+// The XFCC header sent by the client never reaches the backend when sanitized
+#[pdk_test]
+async fn sanitize_removes_forwarded_client_cert() -> anyhow::Result<()> {
+    let test = start(serde_json::json!({"forwardClientCertDetails": "sanitize"})).await?;
+
+    // Only answer when the XFCC header was removed
+    test.backend.mock_async(|when, then| {
+        when.path_contains("/hello")
+            .header_missing("x-forwarded-client-cert");
+        then.status(202).body("World!");
+    }).await;
+
+    // Perform a request spoofing the XFCC header
+    let response = reqwest::Client::new()
+        .get(format!("{}/hello", test.flex_url))
+        .header("x-forwarded-client-cert", "Hash=1234;Subject=\"CN=Joker\"")
+        .send()
+        .await?;
+
+    assert_eq!(response.status(), 202);
+
+    Ok(())
+}
+
+// The CRLs are fetched from the configured service when the policy starts
+#[pdk_test]
+async fn crl_is_fetched_from_service() -> anyhow::Result<()> {
+    let test = start(serde_json::json!({
+        "crl": {
+            "issuers": [include_str!("resources/ca.pem")],
+            "service": "http://backend:80",
+            "path": "/crl.pem",
+            "failOpen": true
+        },
+        "peerCertificateAvailable": true
+    }))
+    .await?;
+
+    // Serve the CRL of the test CA
+    let crl_mock = test.backend.mock_async(|when, then| {
+        when.path("/crl.pem");
+        then.status(200).body(include_str!("resources/crl.pem"));
+    }).await;
+
+    test.backend.mock_async(|when, then| {
+        when.path_contains("/hello");
+        then.status(202).body("World!");
+    }).await;
+
+    // Requests without a client certificate are not checked
+    let response = reqwest::get(format!("{}/hello", test.flex_url)).await?;
+    assert_eq!(response.status(), 202);
+
+    // Every worker fetches the CRLs on its own
+    assert!(crl_mock.hits_async().await >= 1);
+
+    Ok(())
+}
+
+// Tokens bound to a certificate are rejected when the request has no client certificate
+#[pdk_test]
+async fn bound_token_without_certificate_is_rejected() -> anyhow::Result<()> {
+    let test = start(serde_json::json!({"checkBoundTokens": true})).await?;
+
+    test.backend.mock_async(|when, then| {
+        when.path_contains("/hello");
+        then.status(202).body("World!");
+    }).await;
+
+    // Token with a cnf.x5t#S256 claim, its signature is not checked by the policy
+    let token = "eyJhbGciOiJIUzI1NiIsInR5cCI6IkpXVCJ9.\
+        eyJjbmYiOnsieDV0I1MyNTYiOiJqLUVBcUlpS0ZPRmlwVk1rb3h5Y0dnY05remdVdHl5cHFTQVJGUTBUd0dNIn0sInN1YiI6Ikpva2VyIn0.\
+        c2lnbmF0dXJl";
+    let response = reqwest::Client::new()
+        .get(format!("{}/hello", test.flex_url))
+        .bearer_auth(token)
+        .send()
+        .await?;
+    assert_eq!(response.status(), 401);
+
+    // Requests without token are left to the other policies
+    let response = reqwest::get(format!("{}/hello", test.flex_url)).await?;
+    assert_eq!(response.status(), 202);
+
+    Ok(())
+}
+
+// The headers of the policy follow the configured prefix, and clients can not spoof them
+#[pdk_test]
+async fn headers_follow_prefix() -> anyhow::Result<()> {
+    let test = start(serde_json::json!({"headerPrefix": "X-Client-Cert-"})).await?;
+
+    // Only answer when the prefixed header was set and the spoofed one removed
+    test.backend.mock_async(|when, then| {
+        when.path_contains("/hello")
+            .header("x-client-cert-certificate-present", "false")
+            .header_missing("x-client-cert-name");
+        then.status(202).body("World!");
+    }).await;
+
+    // Perform a request spoofing a header of the policy
+    let response = reqwest::Client::new()
+        .get(format!("{}/hello", test.flex_url))
+        .header("X-Client-Cert-Name", "Joker")
+        .send()
+        .await?;
+
+    assert_eq!(response.status(), 202);
+
+    Ok(())
+}
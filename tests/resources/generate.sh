@@ -0,0 +1,80 @@
+#!/bin/sh
+# Regenerates the test CA, client certificate, CRL, OCSP messages and JWT key used by the unit tests.
+set -e
+cd "$(dirname "$0")"
+
+openssl req -x509 -newkey ec -pkeyopt ec_paramgen_curve:P-256 -nodes -keyout ca.key -out ca.pem \
+  -subj "/C=US/O=ACME/CN=Internal CA 2" -not_before 20250101000000Z -not_after 21250101000000Z \
+  -addext "basicConstraints=critical,CA:TRUE" -addext "keyUsage=critical,keyCertSign,cRLSign"
+
+openssl req -new -newkey ec -pkeyopt ec_paramgen_curve:P-256 -nodes -keyout client.key -out client.csr \
+  -config client.cnf -multivalue-rdn \
+  -subj "/DC=com/DC=example/O=Phantom Thieves/OU=Billing+OU=Payments/CN=Joker/emailAddress=joker@example.com"
+openssl x509 -req -in client.csr -CA ca.pem -CAkey ca.key -set_serial 0x1a2b3c4d5e6f \
+  -not_before 20250101000000Z -not_after 21250101000000Z -extfile client.cnf -extensions client -out client.pem
+rm client.csr
+
+# CRL of the CA revoking the client certificate and serial 0102
+cat > crl.cnf <<CNF
+[ca]
+default_ca = ca
+
+[ca]
+database = index.txt
+default_md = sha256
+crlnumber = crlnumber
+CNF
+printf 'R\t21250101000000Z\t250601000000Z\t1A2B3C4D5E6F\tunknown\t/CN=Joker\n' > index.txt
+printf 'R\t21250101000000Z\t250701000000Z\t0102\tunknown\t/CN=Other\n' >> index.txt
+echo 01 > crlnumber
+openssl ca -gencrl -config crl.cnf -keyfile ca.key -cert ca.pem -out crl.pem \
+  -crl_lastupdate 20250101000000Z -crl_nextupdate 21250101000000Z
+rm crl.cnf index.txt* crlnumber*
+
+# OCSP request for the client certificate, and the responses of a responder signing with the CA key
+openssl ocsp -issuer ca.pem -cert client.pem -no_nonce -reqout ocsp-request.der
+printf 'V\t21250101000000Z\t\t1A2B3C4D5E6F\tunknown\t/CN=Joker\n' > index.txt
+openssl ocsp -index index.txt -rsigner ca.pem -rkey ca.key -CA ca.pem -reqin ocsp-request.der -respout ocsp-good.der
+printf 'R\t21250101000000Z\t250601000000Z\t1A2B3C4D5E6F\tunknown\t/CN=Joker\n' > index.txt
+openssl ocsp -index index.txt -rsigner ca.pem -rkey ca.key -CA ca.pem -reqin ocsp-request.der -respout ocsp-revoked.der \
+  -ndays 36500
+rm index.txt
+
+# RSA key signing the JWTs of the unit tests
+openssl genrsa -out jwt-rsa.key 2048
+
+# Server certificate of the gateway in the mTLS integration tests
+openssl req -new -newkey ec -pkeyopt ec_paramgen_curve:P-256 -nodes -keyout server.key -out server.csr \
+  -subj "/O=ACME/CN=local-flex"
+openssl x509 -req -in server.csr -CA ca.pem -CAkey ca.key -set_serial 0x5e -not_before 20250101000000Z \
+  -not_after 21250101000000Z -extfile /dev/stdin -out server.pem <<EXT
+subjectAltName=DNS:local-flex,DNS:localhost,IP:127.0.0.1
+extendedKeyUsage=serverAuth
+EXT
+rm server.csr
+
+# Responses of a delegated OCSP responder, and of a certificate of the CA not allowed to sign OCSP responses
+openssl req -new -newkey ec -pkeyopt ec_paramgen_curve:P-256 -nodes -keyout ocsp-responder.key \
+  -out ocsp-responder.csr -subj "/O=ACME/CN=OCSP Responder"
+openssl x509 -req -in ocsp-responder.csr -CA ca.pem -CAkey ca.key -set_serial 0x0c5b -not_before 20250101000000Z \
+  -not_after 21250101000000Z -extfile /dev/stdin -out ocsp-responder.pem <<EXT
+extendedKeyUsage=OCSPSigning
+EXT
+printf 'V\t21250101000000Z\t\t1A2B3C4D5E6F\tunknown\t/CN=Joker\n' > index.txt
+openssl ocsp -index index.txt -rsigner ocsp-responder.pem -rkey ocsp-responder.key -CA ca.pem \
+  -reqin ocsp-request.der -respout ocsp-delegated.der -ndays 36500
+openssl ocsp -index index.txt -rsigner server.pem -rkey server.key -CA ca.pem \
+  -reqin ocsp-request.der -respout ocsp-unauthorized.der -ndays 36500
+rm index.txt ocsp-responder.csr ocsp-responder.key ocsp-responder.pem
+
+# Response of a delegated OCSP responder with a P-384 key, signed with SHA-384
+openssl req -new -newkey ec -pkeyopt ec_paramgen_curve:P-384 -nodes -keyout ocsp-responder-p384.key \
+  -out ocsp-responder-p384.csr -subj "/O=ACME/CN=OCSP Responder P-384"
+openssl x509 -req -in ocsp-responder-p384.csr -CA ca.pem -CAkey ca.key -set_serial 0x0c5c -not_before 20250101000000Z \
+  -not_after 21250101000000Z -extfile /dev/stdin -out ocsp-responder-p384.pem <<EXT
+extendedKeyUsage=OCSPSigning
+EXT
+printf 'V\t21250101000000Z\t\t1A2B3C4D5E6F\tunknown\t/CN=Joker\n' > index.txt
+openssl ocsp -index index.txt -rsigner ocsp-responder-p384.pem -rkey ocsp-responder-p384.key -rmd sha384 -CA ca.pem \
+  -reqin ocsp-request.der -respout ocsp-delegated-p384.der -ndays 36500
+rm index.txt ocsp-responder-p384.csr ocsp-responder-p384.key ocsp-responder-p384.pem